@@ -1,36 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::{ApiError, Error};
+use crate::http::{HttpClient, ReqwestHttpClient};
+use crate::provider::TextCallback;
+use crate::sse;
+
 const ANTHROPIC_VERSION: &str = "2023-06-01";
 const BASE_URL: &str = "https://api.anthropic.com";
 
-#[async_trait::async_trait]
-pub trait HttpClient {
-    async fn post_json<T: Serialize + Send + Sync>(
-        &self,
-        url: &str,
-        headers: Vec<(String, String)>,
-        body: &T,
-    ) -> Result<String, Box<dyn std::error::Error>>;
-}
-
-pub struct ReqwestHttpClient {
-    client: reqwest::Client,
-}
-
-impl ReqwestHttpClient {
-    pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-        }
-    }
-}
-
-impl Default for ReqwestHttpClient {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 pub struct Client<H: HttpClient> {
     http_client: H,
     api_key: String,
@@ -48,11 +25,25 @@ pub struct MessageNewParams {
     model: String,
     max_tokens: u64,
     messages: Vec<MessageParam>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Deserialize)]
 pub struct Message {
     pub content: Vec<MessageContent>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
 }
 
 #[derive(Deserialize)]
@@ -62,18 +53,130 @@ pub struct MessageContent {
     pub text: String,
 }
 
+// Events of a streamed response, see
+// https://docs.anthropic.com/en/api/messages-streaming
 #[derive(Deserialize)]
-struct ErrorResponse {
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart {
+        #[serde(default)]
+        message: StartedMessage,
+    },
+    ContentBlockStart,
+    ContentBlockDelta {
+        delta: ContentDelta,
+    },
+    ContentBlockStop,
+    MessageDelta {
+        #[serde(default)]
+        usage: Option<Usage>,
+    },
+    MessageStop,
+    Ping,
+    Error {
+        error: StreamError,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Default, Deserialize)]
+struct StartedMessage {
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentDelta {
+    TextDelta {
+        text: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize)]
+struct StreamError {
     message: String,
     #[serde(rename = "type")]
     error_type: String,
 }
 
-#[derive(Deserialize)]
-struct ErrorWrapper {
-    error: ErrorResponse,
-    #[serde(rename = "type")]
-    response_type: String,
+#[derive(Default)]
+struct MessageStream {
+    text: String,
+    usage: Option<Usage>,
+    started: bool,
+    stopped: bool,
+    error: Option<Error>,
+}
+
+impl MessageStream {
+    fn handle(&mut self, event: &sse::Event, on_text: &mut TextCallback<'_>) {
+        if self.stopped || self.error.is_some() {
+            return;
+        }
+
+        let stream_event: StreamEvent = match serde_json::from_str(&event.data) {
+            Ok(stream_event) => stream_event,
+            Err(e) => {
+                self.error = Some(e.into());
+                return;
+            }
+        };
+
+        match stream_event {
+            StreamEvent::MessageStart { message } => {
+                self.started = true;
+                self.usage = message.usage;
+            }
+            StreamEvent::ContentBlockDelta {
+                delta: ContentDelta::TextDelta { text },
+            } => {
+                on_text(&text);
+                self.text.push_str(&text);
+            }
+            // The output token count is cumulative, the last delta has the total
+            StreamEvent::MessageDelta { usage: Some(delta) } => {
+                let usage = self.usage.get_or_insert_default();
+                usage.output_tokens = delta.output_tokens;
+            }
+            StreamEvent::MessageStop => self.stopped = true,
+            StreamEvent::Error { error } => {
+                self.error = Some(Error::from_api_error(ApiError {
+                    error_type: Some(error.error_type),
+                    message: error.message,
+                    ..ApiError::default()
+                }));
+            }
+            StreamEvent::ContentBlockDelta { .. }
+            | StreamEvent::ContentBlockStart
+            | StreamEvent::ContentBlockStop
+            | StreamEvent::MessageDelta { usage: None }
+            | StreamEvent::Ping
+            | StreamEvent::Unknown => {}
+        }
+    }
+
+    fn into_message(self) -> Result<Message, Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if !self.started || !self.stopped {
+            return Err(Error::Network(
+                "message stream ended unexpectedly".to_string(),
+            ));
+        }
+
+        Ok(Message {
+            content: vec![MessageContent {
+                message_content_type: "text".to_string(),
+                text: self.text,
+            }],
+            usage: self.usage,
+        })
+    }
 }
 
 impl MessageNewParams {
@@ -82,8 +185,15 @@ impl MessageNewParams {
             model,
             max_tokens,
             messages,
+            temperature: None,
+            stream: false,
         }
     }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
 }
 
 impl MessageParam {
@@ -93,6 +203,13 @@ impl MessageParam {
             content,
         }
     }
+
+    pub fn assistant(content: String) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content,
+        }
+    }
 }
 
 impl Client<ReqwestHttpClient> {
@@ -110,69 +227,74 @@ impl<H: HttpClient> Client<H> {
         }
     }
 
-    pub async fn new_message(
-        &self,
-        message_new_params: MessageNewParams,
-    ) -> Result<Message, Box<dyn std::error::Error>> {
-        let api_url = format!("{}/v1/messages", self.api_base_url);
-        let headers = vec![
+    pub fn with_base_url(mut self, api_base_url: String) -> Self {
+        self.api_base_url = api_base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        vec![
             ("x-api-key".to_string(), self.api_key.to_string()),
             (
                 "anthropic-version".to_string(),
                 ANTHROPIC_VERSION.to_string(),
             ),
             ("content-type".to_string(), "application/json".to_string()),
-        ];
+        ]
+    }
+
+    pub async fn new_message(
+        &self,
+        message_new_params: MessageNewParams,
+    ) -> Result<Message, Error> {
+        let api_url = format!("{}/v1/messages", self.api_base_url);
         let response = self
             .http_client
-            .post_json(&api_url, headers, &message_new_params)
+            .post_json(&api_url, self.headers(), &message_new_params)
             .await?;
 
         let message: Message = serde_json::from_str(&response)?;
         Ok(message)
     }
-}
 
-#[async_trait::async_trait]
-impl HttpClient for ReqwestHttpClient {
-    async fn post_json<T: Serialize + Send + Sync>(
+    /// Sends the message with `"stream": true`, calling `on_text` with each
+    /// text delta as it arrives. Returns the assembled message.
+    pub async fn new_message_stream(
         &self,
-        url: &str,
-        headers: Vec<(String, String)>,
-        body: &T,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let mut header_map = reqwest::header::HeaderMap::new();
-        for (key, value) in headers {
-            header_map.insert(
-                reqwest::header::HeaderName::from_bytes(key.as_bytes())?,
-                reqwest::header::HeaderValue::from_str(&value)?,
-            );
-        }
+        mut message_new_params: MessageNewParams,
+        on_text: &mut TextCallback<'_>,
+    ) -> Result<Message, Error> {
+        message_new_params.stream = true;
 
-        let response = self
-            .client
-            .post(url)
-            .headers(header_map)
-            .json(body)
-            .send()
+        let api_url = format!("{}/v1/messages", self.api_base_url);
+        let mut decoder = sse::Decoder::new();
+        let mut stream = MessageStream::default();
+
+        self.http_client
+            .post_json_stream(
+                &api_url,
+                self.headers(),
+                &message_new_params,
+                &mut |chunk| {
+                    for event in decoder.feed(chunk) {
+                        stream.handle(&event, on_text);
+                    }
+                },
+            )
             .await?;
 
-        if !response.status().is_success() {
-            let error: ErrorWrapper = response.json().await?;
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Anthropic API error: {}", error.error.message),
-            )));
+        if let Some(event) = decoder.finish() {
+            stream.handle(&event, on_text);
         }
 
-        let response_text = response.text().await?;
-        Ok(response_text)
+        stream.into_message()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::ChunkCallback;
     use async_trait::async_trait;
     use serde_json::json;
 
@@ -190,7 +312,7 @@ mod tests {
             url: &str,
             headers: Vec<(String, String)>,
             body: &T,
-        ) -> Result<String, Box<dyn std::error::Error>> {
+        ) -> Result<String, Error> {
             assert_eq!(url, self.expected_url);
 
             for (key, value) in &self.expected_headers {
@@ -205,15 +327,164 @@ mod tests {
             let body_json = serde_json::to_value(body)?;
             assert!(body_json.get("model").is_some());
             assert!(body_json.get("messages").is_some());
+            assert!(body_json.get("stream").is_none());
 
             if self.should_fail {
-                Err("Mock error".into())
+                Err(Error::Network("Mock error".to_string()))
             } else {
                 Ok(self.response.clone())
             }
         }
     }
 
+    struct MockStreamingHttpClient {
+        response: String,
+        chunk_size: usize,
+    }
+
+    #[async_trait]
+    impl HttpClient for MockStreamingHttpClient {
+        async fn post_json<T: Serialize + Send + Sync>(
+            &self,
+            _url: &str,
+            _headers: Vec<(String, String)>,
+            _body: &T,
+        ) -> Result<String, Error> {
+            unreachable!("streaming requests go through post_json_stream")
+        }
+
+        async fn post_json_stream<T: Serialize + Send + Sync>(
+            &self,
+            _url: &str,
+            _headers: Vec<(String, String)>,
+            body: &T,
+            on_chunk: &mut ChunkCallback<'_>,
+        ) -> Result<(), Error> {
+            let body_json = serde_json::to_value(body)?;
+            assert_eq!(body_json.get("stream"), Some(&json!(true)));
+
+            for chunk in self.response.as_bytes().chunks(self.chunk_size) {
+                on_chunk(chunk);
+            }
+
+            Ok(())
+        }
+    }
+
+    fn sse_event(data: serde_json::Value) -> String {
+        format!(
+            "event: {}\ndata: {}\n\n",
+            data["type"].as_str().unwrap(),
+            data
+        )
+    }
+
+    fn sse_text_stream(deltas: &[&str]) -> String {
+        let mut response = sse_event(json!({
+            "type": "message_start",
+            "message": { "id": "msg_1", "type": "message", "role": "assistant", "content": [],
+                "usage": { "input_tokens": 25, "output_tokens": 1 } }
+        }));
+        response.push_str(&sse_event(json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": { "type": "text", "text": "" }
+        })));
+        response.push_str(&sse_event(json!({ "type": "ping" })));
+        for delta in deltas {
+            response.push_str(&sse_event(json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": delta }
+            })));
+        }
+        response.push_str(&sse_event(
+            json!({ "type": "content_block_stop", "index": 0 }),
+        ));
+        response.push_str(&sse_event(json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": "end_turn" },
+            "usage": { "output_tokens": 12 }
+        })));
+        response.push_str(&sse_event(json!({ "type": "message_stop" })));
+        response
+    }
+
+    fn stream_params() -> MessageNewParams {
+        MessageNewParams::new(
+            "claude-3-5-sonnet-20240620".to_string(),
+            1024,
+            vec![MessageParam::new("Test prompt".to_string())],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_new_message_stream_success() {
+        let mock_client = MockStreamingHttpClient {
+            response: sse_text_stream(&["feat: Add ", "new authentication", " system"]),
+            chunk_size: 7,
+        };
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let mut received = Vec::new();
+        let message = client
+            .new_message_stream(stream_params(), &mut |text| received.push(text.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            received,
+            vec!["feat: Add ", "new authentication", " system"]
+        );
+        assert_eq!(message.content.len(), 1);
+        assert_eq!(
+            message.content[0].text,
+            "feat: Add new authentication system"
+        );
+        let usage = message.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (25, 12));
+    }
+
+    #[tokio::test]
+    async fn test_new_message_stream_error_event() {
+        let mut response = sse_text_stream(&[]);
+        response.insert_str(
+            0,
+            &sse_event(json!({
+                "type": "error",
+                "error": { "type": "overloaded_error", "message": "Overloaded" }
+            })),
+        );
+        let mock_client = MockStreamingHttpClient {
+            response,
+            chunk_size: 64,
+        };
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let result = client
+            .new_message_stream(stream_params(), &mut |_| {})
+            .await;
+
+        assert!(matches!(result, Err(Error::Overloaded(_))));
+    }
+
+    #[tokio::test]
+    async fn test_new_message_stream_truncated() {
+        let response = sse_text_stream(&["feat: Add"]);
+        let truncated = &response[..response.find("event: content_block_stop").unwrap()];
+        let mock_client = MockStreamingHttpClient {
+            response: truncated.to_string(),
+            chunk_size: 64,
+        };
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let result = client
+            .new_message_stream(stream_params(), &mut |_| {})
+            .await;
+
+        assert!(matches!(result, Err(Error::Network(_))));
+    }
+
     #[tokio::test]
     async fn test_new_message_success() {
         let mock_response = json!({
@@ -1,15 +1,258 @@
-use crate::anthropic;
+use futures::{StreamExt, TryStreamExt, stream};
 
-const ANTHROPIC_MODEL: &str = "claude-3-5-sonnet-20240620";
+use crate::error::Error;
+use crate::lint::Rules;
+use crate::prompt::{build_repair_prompt, build_summary_prompt};
+use crate::provider::{Message, Provider, TextCallback};
+use crate::repair::{repair, validation_errors};
 
-pub async fn generate_commit_message(
-    api_key: &str,
+// Summary requests in flight at once, low enough to stay clear of rate limits
+const SUMMARY_CONCURRENCY: usize = 4;
+
+/// Sends `prompt` and returns the answer, whether a commit message, a pull
+/// request description or anything else the prompt asks for.
+pub async fn generate(provider: &dyn Provider, prompt: &str) -> Result<String, Error> {
+    let message = provider.complete(prompt).await?;
+    Ok(message.trim().to_string())
+}
+
+/// Generates the commit message, reporting text through `on_text` while the
+/// provider is still producing it.
+pub async fn stream_commit_message(
+    provider: &dyn Provider,
+    prompt: &str,
+    on_text: &mut TextCallback<'_>,
+) -> Result<String, Error> {
+    let message = provider.complete_stream(prompt, on_text).await?;
+    Ok(message.trim().to_string())
+}
+
+/// Asks for a new message, continuing a conversation that holds the prompt,
+/// the earlier messages and the user's feedback on each of them.
+pub async fn refine_commit_message(
+    provider: &dyn Provider,
+    conversation: &[Message],
+    on_text: &mut TextCallback<'_>,
+) -> Result<String, Error> {
+    let message = provider.chat_stream(conversation, on_text).await?;
+    Ok(message.trim().to_string())
+}
+
+/// Cleans up a message the model wrote for `prompt`. While it is still not
+/// the conventional commit the rules require, the model is told what is
+/// wrong and asked again, at most `attempts` times.
+pub async fn repair_commit_message(
+    provider: &dyn Provider,
+    prompt: &str,
+    message: &str,
+    rules: &Rules,
+    attempts: u32,
+) -> Result<String, Error> {
+    let mut message = repair(message);
+    let mut conversation = vec![Message::user(prompt.to_string())];
+
+    for _ in 0..attempts {
+        let errors = validation_errors(&message, rules);
+        if errors.is_empty() {
+            break;
+        }
+        conversation.push(Message::assistant(message));
+        conversation.push(Message::user(build_repair_prompt(&errors)));
+        message = repair(&provider.chat(&conversation).await?);
+    }
+
+    Ok(message)
+}
+
+/// Asks for `count` messages at once, dropping duplicates.
+pub async fn generate_candidates(
+    provider: &dyn Provider,
     prompt: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let client = anthropic::Client::new_default(api_key.to_string());
-    let message = anthropic::MessageParam::new(prompt.to_string());
-    let message_new_params =
-        anthropic::MessageNewParams::new(ANTHROPIC_MODEL.to_string(), 1000, vec![message]);
-    let message = client.new_message(message_new_params).await?;
-    Ok(message.content.first().unwrap().text.clone())
+    count: usize,
+) -> Result<Vec<String>, Error> {
+    let messages =
+        futures::future::try_join_all((0..count).map(|_| generate(provider, prompt))).await?;
+
+    let mut candidates: Vec<String> = Vec::new();
+    for message in messages {
+        if !candidates.contains(&message) {
+            candidates.push(message);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Summarizes each chunk of a diff too large for a single prompt, a few
+/// chunks at a time, and joins the summaries in the original order.
+pub async fn summarize_diff(provider: &dyn Provider, chunks: &[String]) -> Result<String, Error> {
+    let total = chunks.len();
+    let summaries: Vec<String> = stream::iter(chunks.iter().enumerate())
+        .map(|(index, chunk)| async move {
+            let prompt = build_summary_prompt(chunk, index + 1, total);
+            let summary = provider.complete(&prompt).await?;
+            Ok::<_, Error>(format!("Part {}:\n{}", index + 1, summary.trim()))
+        })
+        .buffered(SUMMARY_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    Ok(summaries.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Answers with the part number from the prompt, later parts faster
+    struct PartProvider;
+
+    #[async_trait::async_trait]
+    impl Provider for PartProvider {
+        async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+            let part: u64 = messages[0]
+                .content
+                .split("This is part ")
+                .nth(1)
+                .and_then(|rest| rest.split(' ').next())
+                .and_then(|part| part.parse().ok())
+                .expect("summary prompt");
+            if part == 3 {
+                return Err(Error::Timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(20 / part)).await;
+            Ok(format!("- summary {}\n", part))
+        }
+    }
+
+    struct CountingProvider {
+        calls: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for CountingProvider {
+        async fn chat(&self, _messages: &[Message]) -> Result<String, Error> {
+            let mut calls = self.calls.lock().unwrap();
+            *calls += 1;
+            // The second and third answers are the same
+            Ok(format!("  Message {}\n", (*calls).min(2)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_candidates_drops_duplicates() {
+        let provider = CountingProvider {
+            calls: std::sync::Mutex::new(0),
+        };
+
+        let candidates = generate_candidates(&provider, "prompt", 3).await.unwrap();
+
+        assert_eq!(candidates, vec!["Message 1", "Message 2"]);
+        assert_eq!(*provider.calls.lock().unwrap(), 3);
+    }
+
+    // Answers with the last feedback and how long the conversation was
+    struct EchoProvider;
+
+    #[async_trait::async_trait]
+    impl Provider for EchoProvider {
+        async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+            let last = messages.last().expect("conversation");
+            Ok(format!(
+                "{} after {} messages\n",
+                last.content,
+                messages.len()
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refine_commit_message_sends_conversation() {
+        let conversation = vec![
+            Message::user("prompt".to_string()),
+            Message::assistant("Add login form".to_string()),
+            Message::user("shorter".to_string()),
+        ];
+        let mut received = String::new();
+
+        let message = refine_commit_message(&EchoProvider, &conversation, &mut |text| {
+            received.push_str(text)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(message, "shorter after 3 messages");
+        assert_eq!(received, "shorter after 3 messages\n");
+    }
+
+    // Answers with a conventional commit once told what was wrong
+    struct LearningProvider {
+        calls: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for LearningProvider {
+        async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+            *self.calls.lock().unwrap() += 1;
+            if messages.len() == 5 {
+                Ok("```\nfeat: add login form\n```".to_string())
+            } else {
+                Ok("Add login form".to_string())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repair_commit_message_asks_again() {
+        let provider = LearningProvider {
+            calls: std::sync::Mutex::new(0),
+        };
+        let rules = Rules {
+            conventional: true,
+            ..Rules::default()
+        };
+
+        let message = repair_commit_message(&provider, "prompt", "```\nAdd login\n```", &rules, 3)
+            .await
+            .unwrap();
+        assert_eq!(message, "feat: add login form");
+        assert_eq!(*provider.calls.lock().unwrap(), 2);
+
+        // Gives up after the last attempt and keeps the last message
+        let message = repair_commit_message(&provider, "prompt", "Add login", &rules, 1)
+            .await
+            .unwrap();
+        assert_eq!(message, "Add login form");
+
+        // Only cleans up when no conventional commit is required
+        let message = repair_commit_message(
+            &provider,
+            "prompt",
+            "```\nAdd login\n```",
+            &Rules::default(),
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(message, "Add login");
+        assert_eq!(*provider.calls.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_diff_keeps_order() {
+        let chunks = vec!["diff a".to_string(), "diff b".to_string()];
+
+        let summary = summarize_diff(&PartProvider, &chunks).await.unwrap();
+
+        assert_eq!(summary, "Part 1:\n- summary 1\n\nPart 2:\n- summary 2");
+    }
+
+    #[tokio::test]
+    async fn test_summarize_diff_fails_if_any_part_fails() {
+        let chunks = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let result = summarize_diff(&PartProvider, &chunks).await;
+
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
 }
@@ -0,0 +1,291 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::conventional::ConventionalCommit;
+use crate::git::git_at;
+
+/// How much a release changes the version, smallest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bump::Patch => write!(f, "patch"),
+            Bump::Minor => write!(f, "minor"),
+            Bump::Major => write!(f, "major"),
+        }
+    }
+}
+
+impl Bump {
+    /// Breaking changes need a major release and features a minor one.
+    /// Everything else, commits that could not be classified included, is a
+    /// patch.
+    pub fn for_commit(commit: Option<&ConventionalCommit>) -> Self {
+        match commit {
+            Some(commit) if commit.breaking => Bump::Major,
+            Some(commit) if commit.commit_type == "feat" => Bump::Minor,
+            _ => Bump::Patch,
+        }
+    }
+
+    /// The bump to release from `version`: before 1.0.0 the API is not
+    /// stable yet, so breaking changes only bump the minor version.
+    pub fn for_version(self, version: Version) -> Self {
+        match self {
+            Bump::Major if version.major == 0 => Bump::Minor,
+            bump => bump,
+        }
+    }
+}
+
+/// The bump the commits since the last release call for, and the subjects
+/// of the commits that call for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recommendation {
+    pub bump: Bump,
+    pub reasons: Vec<String>,
+}
+
+impl Recommendation {
+    /// `commits` holds the classification of each message, `None` for those
+    /// that could not be classified.
+    pub fn for_commits(messages: &[String], commits: &[Option<ConventionalCommit>]) -> Self {
+        let bumps: Vec<Bump> = commits
+            .iter()
+            .map(|commit| Bump::for_commit(commit.as_ref()))
+            .collect();
+        let bump = bumps.iter().copied().max().unwrap_or(Bump::Patch);
+        let reasons = messages
+            .iter()
+            .zip(&bumps)
+            .filter(|(_, commit_bump)| **commit_bump == bump)
+            .map(|(message, _)| message.lines().next().unwrap_or_default().to_string())
+            .collect();
+
+        Self { bump, reasons }
+    }
+
+    /// Why the commits call for this bump, to go before the list of reasons.
+    pub fn justification(&self) -> &'static str {
+        match self.bump {
+            Bump::Major => "Breaking changes",
+            Bump::Minor => "New features, without breaking changes",
+            Bump::Patch => "Fixes and other changes, without new features or breaking changes",
+        }
+    }
+}
+
+/// A release version, without pre-release or build metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Version {
+    /// Parses tags such as `1.2.3` or `v1.2.3`.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let mut parts = tag.strip_prefix('v').unwrap_or(tag).split('.');
+        let mut number = || parts.next()?.parse::<u64>().ok();
+        let version = Self {
+            major: number()?,
+            minor: number()?,
+            patch: number()?,
+        };
+
+        parts.next().is_none().then_some(version)
+    }
+
+    pub fn bump(self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Self {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Self {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Bump::Patch => Self {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+/// The highest version tag reachable from HEAD, with its version.
+pub fn latest_tag_at(path: &Path) -> Result<Option<(String, Version)>, Box<dyn std::error::Error>> {
+    let tags = git_at(path, &["tag", "--merged", "HEAD"])?;
+
+    Ok(tags
+        .lines()
+        .filter_map(|tag| Version::parse(tag).map(|version| (tag.to_string(), version)))
+        .max_by_key(|(_, version)| *version))
+}
+
+/// Creates an annotated tag on HEAD, keeping the message as it is instead of
+/// dropping its Markdown headings as comments.
+pub fn create_tag_at(
+    path: &Path,
+    tag: &str,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    git_at(
+        path,
+        &[
+            "tag",
+            "--annotate",
+            "--cleanup=verbatim",
+            "-m",
+            message,
+            tag,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn version(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn test_version_parse_and_bump() {
+        assert_eq!(Version::parse("v1.2.3"), Some(version(1, 2, 3)));
+        assert_eq!(Version::parse("0.10.0"), Some(version(0, 10, 0)));
+        assert_eq!(Version::parse("v1.2"), None);
+        assert_eq!(Version::parse("v1.2.3.4"), None);
+        assert_eq!(Version::parse("v1.2.3-rc.1"), None);
+        assert_eq!(Version::parse("release-1"), None);
+
+        assert_eq!(version(1, 2, 3).bump(Bump::Major), version(2, 0, 0));
+        assert_eq!(version(1, 2, 3).bump(Bump::Minor), version(1, 3, 0));
+        assert_eq!(version(1, 2, 3).bump(Bump::Patch), version(1, 2, 4));
+        assert_eq!(version(1, 2, 3).to_string(), "1.2.3");
+    }
+
+    #[test]
+    fn test_bump_for_commit() {
+        let bump = |message: &str| Bump::for_commit(ConventionalCommit::parse(message).as_ref());
+
+        assert_eq!(bump("feat(api)!: change response format"), Bump::Major);
+        assert_eq!(
+            bump("fix: prevent SQL injection\n\nBREAKING CHANGE: sanitize() is async"),
+            Bump::Major
+        );
+        assert_eq!(bump("feat: add login"), Bump::Minor);
+        assert_eq!(bump("perf: cache parsed templates"), Bump::Patch);
+        assert_eq!(bump("Update things"), Bump::Patch);
+        assert!(Bump::Major > Bump::Minor && Bump::Minor > Bump::Patch);
+
+        let current = version(0, 3, 1);
+        assert_eq!(Bump::Major.for_version(current), Bump::Minor);
+        assert_eq!(
+            current.bump(Bump::Major.for_version(current)),
+            version(0, 4, 0)
+        );
+        assert_eq!(Bump::Patch.for_version(current), Bump::Patch);
+        assert_eq!(Bump::Major.for_version(version(1, 0, 0)), Bump::Major);
+    }
+
+    #[test]
+    fn test_recommendation() {
+        let recommend = |messages: &[&str]| {
+            let messages: Vec<String> = messages.iter().map(|m| m.to_string()).collect();
+            let commits: Vec<_> = messages
+                .iter()
+                .map(|message| ConventionalCommit::parse(message))
+                .collect();
+            Recommendation::for_commits(&messages, &commits)
+        };
+
+        assert_eq!(
+            recommend(&[
+                "fix: resolve race",
+                "feat: add login",
+                "feat(api): add search"
+            ]),
+            Recommendation {
+                bump: Bump::Minor,
+                reasons: vec![
+                    "feat: add login".to_string(),
+                    "feat(api): add search".to_string()
+                ],
+            }
+        );
+        assert_eq!(
+            recommend(&[
+                "feat: add login",
+                "chore!: drop Node 16\n\nNode 18 is required."
+            ]),
+            Recommendation {
+                bump: Bump::Major,
+                reasons: vec!["chore!: drop Node 16".to_string()],
+            }
+        );
+        assert_eq!(
+            recommend(&["docs: fix typo", "Update things"]).bump,
+            Bump::Patch
+        );
+    }
+
+    #[test]
+    fn test_latest_tag_and_create_tag() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        let commit =
+            |message: &str| git_at(repo, &["commit", "-q", "--allow-empty", "-m", message]);
+        git_at(repo, &["init", "-q"])?;
+        git_at(repo, &["config", "user.name", "Test"])?;
+        git_at(repo, &["config", "user.email", "test@example.com"])?;
+        commit("Initial")?;
+        assert_eq!(latest_tag_at(repo)?, None);
+
+        for tag in ["v1.9.0", "v1.10.0", "nightly", "v2.0.0-rc.1"] {
+            git_at(repo, &["tag", tag])?;
+        }
+        commit("Add login")?;
+        assert_eq!(
+            latest_tag_at(repo)?,
+            Some(("v1.10.0".to_string(), version(1, 10, 0)))
+        );
+
+        create_tag_at(
+            repo,
+            "v1.11.0",
+            "Release 1.11.0\n\n### Added\n\n- Add login",
+        )?;
+        assert_eq!(
+            latest_tag_at(repo)?,
+            Some(("v1.11.0".to_string(), version(1, 11, 0)))
+        );
+        let message = git_at(repo, &["tag", "-l", "--format=%(contents)", "v1.11.0"])?;
+        assert!(message.contains("### Added"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::conventional::ConventionalCommit;
+
+pub const CHANGELOG_FILE: &str = "CHANGELOG.md";
+const CHANGELOG_HEADER: &str = "# Changelog\n\nAll notable changes to this project will be documented in this file.\n\nThe format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).\n";
+
+/// The Keep a Changelog sections commits can end up in, in their usual order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Added,
+    Changed,
+    Fixed,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::Added => write!(f, "Added"),
+            Section::Changed => write!(f, "Changed"),
+            Section::Fixed => write!(f, "Fixed"),
+        }
+    }
+}
+
+impl Section {
+    /// Where a commit of this type goes. Types that do not change what users
+    /// get, such as `docs` or `ci`, are left out of the changelog.
+    pub fn for_type(commit_type: &str) -> Option<Self> {
+        match commit_type {
+            "feat" => Some(Section::Added),
+            "fix" => Some(Section::Fixed),
+            "perf" | "refactor" | "revert" => Some(Section::Changed),
+            _ => None,
+        }
+    }
+}
+
+/// One line of the changelog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub section: Section,
+    pub text: String,
+    pub breaking: bool,
+}
+
+impl Entry {
+    /// The entry for a conventional commit, `None` if its type is not one
+    /// for the changelog. Breaking changes are always listed, under Changed
+    /// if their type has no section.
+    pub fn from_conventional(commit: &ConventionalCommit) -> Option<Self> {
+        let section = Section::for_type(&commit.commit_type)
+            .or(commit.breaking.then_some(Section::Changed))?;
+        let description = capitalize(&commit.description);
+        let text = match &commit.scope {
+            Some(scope) if !scope.is_empty() => format!("**{}:** {}", scope, description),
+            _ => description,
+        };
+
+        Some(Self {
+            section,
+            text,
+            breaking: commit.breaking,
+        })
+    }
+
+    /// The entry for a commit that could not be classified, listed under
+    /// Changed rather than dropped.
+    pub fn unclassified(subject: &str) -> Self {
+        Self {
+            section: Section::Changed,
+            text: subject.to_string(),
+            breaking: false,
+        }
+    }
+}
+
+/// Renders a release section, breaking changes first in each section.
+pub fn render(heading: &str, entries: &[Entry]) -> String {
+    format!("## {}\n{}", heading, render_notes(entries))
+}
+
+/// The release notes of a section without its heading, each part preceded
+/// by a blank line.
+pub fn render_notes(entries: &[Entry]) -> String {
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|entry| (entry.section, !entry.breaking));
+
+    let mut text = String::new();
+    let mut section = None;
+    for entry in &entries {
+        if section != Some(entry.section) {
+            text.push_str(&format!("\n### {}\n\n", entry.section));
+            section = Some(entry.section);
+        }
+        let breaking = if entry.breaking { "**Breaking:** " } else { "" };
+        text.push_str(&format!("- {}{}\n", breaking, entry.text));
+    }
+    if entries.is_empty() {
+        text.push_str("\nNo notable changes.\n");
+    }
+
+    text
+}
+
+/// Puts the section above the latest release in the changelog file,
+/// creating it if needed.
+pub fn prepend(changelog_file: &Path, section: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let existing = if changelog_file.exists() {
+        fs::read_to_string(changelog_file)?
+    } else {
+        CHANGELOG_HEADER.to_string()
+    };
+
+    // Keep the title and introduction above the releases
+    let releases = existing
+        .match_indices("## ")
+        .find(|(index, _)| *index == 0 || existing[..*index].ends_with('\n'))
+        .map(|(index, _)| index);
+    let changelog = match releases {
+        Some(index) => format!("{}{}\n{}", &existing[..index], section, &existing[index..]),
+        None => format!("{}\n\n{}", existing.trim_end(), section),
+    };
+
+    fs::write(changelog_file, changelog)?;
+    Ok(())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(message: &str) -> Option<Entry> {
+        Entry::from_conventional(&ConventionalCommit::parse(message).unwrap())
+    }
+
+    #[test]
+    fn test_entry_from_conventional() {
+        assert_eq!(
+            entry("feat(auth): add OAuth2 integration"),
+            Some(Entry {
+                section: Section::Added,
+                text: "**auth:** Add OAuth2 integration".to_string(),
+                breaking: false,
+            })
+        );
+        assert_eq!(entry("fix: resolve race").unwrap().section, Section::Fixed);
+        assert_eq!(entry("docs: update readme"), None);
+        assert_eq!(
+            entry("chore!: drop Node 16").unwrap().section,
+            Section::Changed
+        );
+        assert_eq!(
+            Entry::unclassified("Update things"),
+            Entry {
+                section: Section::Changed,
+                text: "Update things".to_string(),
+                breaking: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let entries = vec![
+            entry("fix: resolve race condition").unwrap(),
+            entry("feat: add login").unwrap(),
+            entry("feat(api)!: change response format").unwrap(),
+        ];
+
+        assert_eq!(
+            render("[1.2.0] - 2026-10-17", &entries),
+            "## [1.2.0] - 2026-10-17\n\n\
+             ### Added\n\n\
+             - **Breaking:** **api:** Change response format\n\
+             - Add login\n\n\
+             ### Fixed\n\n\
+             - Resolve race condition\n"
+        );
+        assert!(render_notes(&entries).starts_with("\n### Added\n\n- **Breaking:**"));
+        assert_eq!(
+            render("[Unreleased]", &[]),
+            "## [Unreleased]\n\nNo notable changes.\n"
+        );
+    }
+
+    #[test]
+    fn test_prepend() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let changelog_file = temp_dir.path().join(CHANGELOG_FILE);
+
+        prepend(
+            &changelog_file,
+            "## [1.0.0] - 2026-01-01\n\n### Added\n\n- Add login\n",
+        )?;
+        let changelog = fs::read_to_string(&changelog_file)?;
+        assert!(changelog.starts_with("# Changelog\n"));
+        assert!(changelog.ends_with("(https://keepachangelog.com/en/1.1.0/).\n\n## [1.0.0] - 2026-01-01\n\n### Added\n\n- Add login\n"));
+
+        prepend(
+            &changelog_file,
+            "## [1.1.0] - 2026-02-01\n\n### Fixed\n\n- Fix login\n",
+        )?;
+        let changelog = fs::read_to_string(&changelog_file)?;
+        let newer = changelog.find("## [1.1.0]").unwrap();
+        let older = changelog.find("## [1.0.0]").unwrap();
+        assert!(changelog.find("# Changelog").unwrap() < newer);
+        assert!(newer < older);
+        assert!(changelog.contains("- Fix login\n\n## [1.0.0]"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,507 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::provider::{ProviderKind, ProviderSettings};
+
+const CONFIG_FILE: &str = "config";
+const REPOSITORY_CONFIG_FILE: &str = ".gitai.toml";
+// What a repository may set. Anything that decides where requests go, or
+// with which key, stays with the user, so a cloned repository cannot send
+// the API key and the diff to a host of its choosing
+const REPOSITORY_SETTINGS: &[&str] = &[
+    "conventional",
+    "scopes",
+    "language",
+    "exclude",
+    "max_diff_tokens",
+    "repair_attempts",
+];
+// Provider settings lived in their own `key = value` file before the config
+// became TOML
+const LEGACY_PROVIDER_FILE: &str = "provider";
+const MODEL_ENV: &str = "GITAI_MODEL";
+const BASE_URL_ENV: &str = "GITAI_BASE_URL";
+
+/// Global settings, stored as TOML in `~/.gitai/config`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Plaintext key written by older versions, moved to the credential
+    /// store the next time it is read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    pub provider: ProviderKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// How many times to ask again for a message that is not the required
+    /// conventional commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repair_attempts: Option<u32>,
+    /// Generate conventional commit messages without passing `-c`.
+    pub conventional: bool,
+    /// Language to write commit messages in, e.g. "German".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Approximate token budget for the diff sent to the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_diff_tokens: Option<usize>,
+    /// Gitignore-style patterns left out of the diff sent to the model, on
+    /// top of the repository's `.gitaiignore`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Allowed conventional commit scopes; any scope is fine when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+impl Config {
+    /// Reads the config, migrating the plain API key file and the legacy
+    /// provider file on first use. A missing config yields the defaults.
+    pub fn load(config_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_file = config_dir.join(CONFIG_FILE);
+        let content = if fs::exists(&config_file)? {
+            fs::read_to_string(&config_file)?
+        } else {
+            String::new()
+        };
+
+        let (mut config, mut migrated) = match toml::from_str::<Config>(&content) {
+            Ok(config) => (config, false),
+            Err(e) => match legacy_api_key(&content) {
+                Some(api_key) => (
+                    Config {
+                        api_key: Some(api_key),
+                        ..Config::default()
+                    },
+                    true,
+                ),
+                None => return Err(format!("Invalid config file {:?}: {}", config_file, e).into()),
+            },
+        };
+
+        let legacy_provider_file = config_dir.join(LEGACY_PROVIDER_FILE);
+        if fs::exists(&legacy_provider_file)? {
+            apply_legacy_provider_settings(
+                &mut config,
+                &fs::read_to_string(&legacy_provider_file)?,
+            )?;
+            migrated = true;
+        }
+
+        if migrated {
+            config.save(config_dir)?;
+            if fs::exists(&legacy_provider_file)? {
+                fs::remove_file(&legacy_provider_file)?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn save(&self, config_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        fs::create_dir_all(config_dir)?;
+
+        let config_file = config_dir.join(CONFIG_FILE);
+        fs::write(&config_file, toml::to_string(self)?)?;
+
+        Ok(config_file)
+    }
+
+    /// Layers the repository's `.gitai.toml`, if any, over this config.
+    /// Settings it contains replace the global ones. Only the style settings
+    /// in `REPOSITORY_SETTINGS` are allowed.
+    pub fn with_repository_config(
+        self,
+        repository_root: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let repository_config_file = repository_root.join(REPOSITORY_CONFIG_FILE);
+        if !fs::exists(&repository_config_file)? {
+            return Ok(self);
+        }
+
+        let invalid = |e: &dyn std::fmt::Display| {
+            format!("Invalid config file {:?}: {}", repository_config_file, e)
+        };
+        let overrides: toml::Table = fs::read_to_string(&repository_config_file)?
+            .parse()
+            .map_err(|e| invalid(&e))?;
+        if overrides.contains_key("api_key") {
+            return Err(invalid(
+                &"api_key is not allowed here, it would be committed with the repository",
+            )
+            .into());
+        }
+        if let Some(key) = overrides
+            .keys()
+            .find(|key| !REPOSITORY_SETTINGS.contains(&key.as_str()))
+        {
+            return Err(invalid(&format!(
+                "{} is not allowed here, set it in ~/.gitai/config. A repository may only set {}",
+                key,
+                REPOSITORY_SETTINGS.join(", ")
+            ))
+            .into());
+        }
+
+        let mut table = toml::Table::try_from(&self)?;
+        table.extend(overrides);
+
+        Ok(table.try_into().map_err(|e| invalid(&e))?)
+    }
+
+    /// Applies `GITAI_MODEL` and `GITAI_BASE_URL`, looked up through `env`.
+    /// Empty variables are ignored.
+    pub fn with_env(mut self, env: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| env(name).filter(|value: &String| !value.is_empty());
+
+        if let Some(model) = var(MODEL_ENV) {
+            self.model = Some(model);
+        }
+        if let Some(base_url) = var(BASE_URL_ENV) {
+            self.base_url = Some(base_url);
+        }
+        self
+    }
+
+    pub fn provider_settings(&self) -> ProviderSettings {
+        ProviderSettings {
+            kind: self.provider,
+            model: self.model.clone(),
+            base_url: self.base_url.clone(),
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            max_attempts: self.max_attempts,
+        }
+    }
+}
+
+// The original config file held nothing but the API key
+fn legacy_api_key(content: &str) -> Option<String> {
+    let api_key = content.trim();
+    let is_bare_token = !api_key.is_empty()
+        && !api_key.contains(|c: char| c.is_whitespace() || c == '=' || c == '[' || c == '"');
+
+    is_bare_token.then(|| api_key.to_string())
+}
+
+fn apply_legacy_provider_settings(
+    config: &mut Config,
+    content: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("Invalid provider setting: '{}'", line).into());
+        };
+        let value = value.trim().to_string();
+
+        match key.trim() {
+            "provider" => config.provider = value.parse()?,
+            "model" if !value.is_empty() => config.model = Some(value),
+            "base_url" if !value.is_empty() => config.base_url = Some(value),
+            "max_attempts" => {
+                config.max_attempts =
+                    Some(value.parse().map_err(|_| {
+                        format!("Invalid max_attempts '{}': expected a number", value)
+                    })?)
+            }
+            "model" | "base_url" => {}
+            other => return Err(format!("Unknown provider setting: '{}'", other).into()),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_missing_config_returns_defaults() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+
+        let config = Config::load(temp_dir.path())?;
+
+        assert_eq!(config, Config::default());
+        assert!(!temp_dir.path().join(CONFIG_FILE).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path().join(".gitai");
+
+        let config = Config {
+            api_key: Some("test-api-key-123".to_string()),
+            provider: ProviderKind::OpenAi,
+            model: Some("gpt-4.1".to_string()),
+            base_url: Some("https://llm.example.com/v1".to_string()),
+            max_tokens: Some(500),
+            temperature: Some(0.25),
+            max_attempts: Some(6),
+            repair_attempts: Some(1),
+            conventional: true,
+            language: Some("German".to_string()),
+            max_diff_tokens: Some(8000),
+            exclude: vec!["*.lock".to_string()],
+            scopes: vec!["api".to_string()],
+        };
+        config.save(&config_dir)?;
+
+        assert_eq!(Config::load(&config_dir)?, config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_partial_config() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join(CONFIG_FILE),
+            "provider = \"ollama\"\nconventional = true\n",
+        )?;
+
+        let config = Config::load(temp_dir.path())?;
+
+        assert_eq!(config.provider, ProviderKind::Ollama);
+        assert!(config.conventional);
+        assert_eq!(config.api_key, None);
+        assert_eq!(config.provider_settings().model(), "llama3.2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join(CONFIG_FILE), "temprature = 0.2\n")?;
+
+        let result = Config::load(temp_dir.path());
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("temprature"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_migrates_plain_api_key() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_file = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_file, "  sk-ant-api03-abc_DEF-123\n\n")?;
+
+        let config = Config::load(temp_dir.path())?;
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-ant-api03-abc_DEF-123"));
+        assert_eq!(config.provider, ProviderKind::Anthropic);
+
+        let migrated: Config = toml::from_str(&fs::read_to_string(&config_file)?)?;
+        assert_eq!(migrated, config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_migrates_legacy_provider_file() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let legacy_provider_file = temp_dir.path().join(LEGACY_PROVIDER_FILE);
+        fs::write(temp_dir.path().join(CONFIG_FILE), "test-api-key-123")?;
+        fs::write(
+            &legacy_provider_file,
+            "provider = openai\nmodel = gpt-4.1\nbase_url = https://llm.example.com/v1\n",
+        )?;
+
+        let config = Config::load(temp_dir.path())?;
+
+        assert_eq!(config.api_key.as_deref(), Some("test-api-key-123"));
+        assert_eq!(config.provider, ProviderKind::OpenAi);
+        assert_eq!(config.model.as_deref(), Some("gpt-4.1"));
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://llm.example.com/v1")
+        );
+        assert!(!legacy_provider_file.exists());
+        assert_eq!(Config::load(temp_dir.path())?, config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_provider_file_unknown_provider() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join(LEGACY_PROVIDER_FILE),
+            "provider = mistral\n",
+        )?;
+
+        let result = Config::load(temp_dir.path());
+
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown provider 'mistral'")
+        );
+        assert!(temp_dir.path().join(LEGACY_PROVIDER_FILE).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_repository_config_overrides_global() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join(REPOSITORY_CONFIG_FILE),
+            "conventional = true\nscopes = [\"api\", \"web\"]\nexclude = []\n",
+        )?;
+        let global = Config {
+            api_key: Some("test-api-key-123".to_string()),
+            provider: ProviderKind::OpenAi,
+            exclude: vec!["*.lock".to_string()],
+            ..Config::default()
+        };
+
+        let config = global.with_repository_config(temp_dir.path())?;
+
+        assert_eq!(config.api_key.as_deref(), Some("test-api-key-123"));
+        assert_eq!(config.provider, ProviderKind::OpenAi);
+        assert!(config.conventional);
+        assert_eq!(config.scopes, vec!["api", "web"]);
+        assert!(config.exclude.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_repository_config_missing_file() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let global = Config {
+            conventional: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            global.clone().with_repository_config(temp_dir.path())?,
+            global
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_repository_config_rejects_api_key() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join(REPOSITORY_CONFIG_FILE),
+            "api_key = \"oops\"\n",
+        )?;
+
+        let result = Config::default().with_repository_config(temp_dir.path());
+
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("api_key is not allowed")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_repository_config_rejects_provider_settings()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        for setting in [
+            "base_url = \"https://attacker.example.com\"",
+            "provider = \"openai\"",
+            "model = \"gpt-4o\"",
+            "max_attempts = 10",
+        ] {
+            fs::write(
+                temp_dir.path().join(REPOSITORY_CONFIG_FILE),
+                format!("conventional = true\n{}\n", setting),
+            )?;
+
+            let result = Config::default().with_repository_config(temp_dir.path());
+
+            let error = result.unwrap_err().to_string();
+            let key = setting.split(' ').next().unwrap();
+            assert!(
+                error.contains(&format!("{} is not allowed", key)),
+                "{}",
+                error
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_repository_config_invalid_value() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join(REPOSITORY_CONFIG_FILE),
+            "conventional = \"yes\"\n",
+        )?;
+
+        let result = Config::default().with_repository_config(temp_dir.path());
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains(".gitai.toml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_env_overrides_model_and_base_url() {
+        let config = Config {
+            model: Some("gpt-4o-mini".to_string()),
+            base_url: Some("https://api.openai.com/v1".to_string()),
+            ..Config::default()
+        };
+
+        let config = config.with_env(|name| match name {
+            MODEL_ENV => Some("gpt-4o".to_string()),
+            BASE_URL_ENV => Some(String::new()),
+            _ => None,
+        });
+
+        assert_eq!(config.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://api.openai.com/v1")
+        );
+    }
+
+    #[test]
+    fn test_legacy_api_key() {
+        assert_eq!(
+            legacy_api_key("sk-ant-123\n").as_deref(),
+            Some("sk-ant-123")
+        );
+        assert_eq!(legacy_api_key(""), None);
+        assert_eq!(legacy_api_key("model = \"x\""), None);
+        assert_eq!(legacy_api_key("[commit]\nfoo"), None);
+    }
+}
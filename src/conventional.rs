@@ -0,0 +1,147 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// The commit types allowed by `CONVENTIONAL_COMMIT_ADDITIONAL_RULES`.
+pub const TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+// <type>[optional scope][!]: <description>
+static SUBJECT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?<type>[A-Za-z]+)(?:\((?<scope>[^()]*)\))?(?<breaking>!)?: (?<description>.*)$")
+        .unwrap()
+});
+static BREAKING_FOOTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^BREAKING[ -]CHANGE: ").unwrap());
+
+/// A message in the conventional commits format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    pub commit_type: String,
+    pub scope: Option<String>,
+    /// Marked with `!` after the type or scope, or a `BREAKING CHANGE:` footer.
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl ConventionalCommit {
+    /// Parses a message whose subject has the shape of a conventional commit,
+    /// without checking the type or the scope.
+    pub fn parse(message: &str) -> Option<Self> {
+        let subject = message.lines().next()?;
+        let captures = SUBJECT.captures(subject)?;
+
+        Some(Self {
+            commit_type: captures["type"].to_string(),
+            scope: captures
+                .name("scope")
+                .map(|scope| scope.as_str().to_string()),
+            breaking: captures.name("breaking").is_some() || has_breaking_footer(message),
+            description: captures["description"].to_string(),
+        })
+    }
+
+    /// Whether the type is one of `TYPES`.
+    pub fn has_known_type(&self) -> bool {
+        TYPES.contains(&self.commit_type.as_str())
+    }
+}
+
+/// Whether the message has a `BREAKING CHANGE:` footer, whatever its subject.
+pub fn has_breaking_footer(message: &str) -> bool {
+    BREAKING_FOOTER.is_match(message)
+}
+
+/// Reads the model's answer to `build_classification_prompt`, one
+/// `<number>: <type>` line per commit. Commits it skipped or gave an unknown
+/// type stay unclassified.
+pub fn parse_classification(response: &str, count: usize) -> Vec<Option<String>> {
+    let mut types = vec![None; count];
+    for line in response.lines() {
+        let Some((number, commit_type)) = line.split_once(':') else {
+            continue;
+        };
+        let commit_type = commit_type.trim().trim_matches('`').to_lowercase();
+        if let Ok(number) = number
+            .trim()
+            .trim_start_matches('-')
+            .trim()
+            .parse::<usize>()
+            && (1..=count).contains(&number)
+            && TYPES.contains(&commit_type.as_str())
+        {
+            types[number - 1] = Some(commit_type);
+        }
+    }
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ConventionalCommit::parse("feat(auth): add OAuth2 integration\n\nWith a body."),
+            Some(ConventionalCommit {
+                commit_type: "feat".to_string(),
+                scope: Some("auth".to_string()),
+                breaking: false,
+                description: "add OAuth2 integration".to_string(),
+            })
+        );
+
+        let commit = ConventionalCommit::parse("fix: resolve race condition").unwrap();
+        assert_eq!(commit.scope, None);
+        assert!(commit.has_known_type());
+
+        assert_eq!(ConventionalCommit::parse("Add login form"), None);
+        assert_eq!(ConventionalCommit::parse("feat add login"), None);
+        assert!(
+            !ConventionalCommit::parse("feature: add login")
+                .unwrap()
+                .has_known_type()
+        );
+    }
+
+    #[test]
+    fn test_parse_breaking() {
+        assert!(
+            ConventionalCommit::parse("feat(api)!: change response format")
+                .unwrap()
+                .breaking
+        );
+        assert!(
+            ConventionalCommit::parse(
+                "fix(database): prevent SQL injection\n\nBREAKING CHANGE: sanitize() now returns a Promise"
+            )
+            .unwrap()
+            .breaking
+        );
+        assert!(
+            !ConventionalCommit::parse("docs: explain BREAKING CHANGE: footers")
+                .unwrap()
+                .breaking
+        );
+        assert!(has_breaking_footer(
+            "Drop Node 16 support\n\nBREAKING-CHANGE: Node 18 is required"
+        ));
+    }
+
+    #[test]
+    fn test_parse_classification() {
+        let response = "1: feat\n- 2: `fix`\n3: improvement\n7: feat\nHere you go";
+
+        assert_eq!(
+            parse_classification(response, 4),
+            vec![
+                Some("feat".to_string()),
+                Some("fix".to_string()),
+                None,
+                None
+            ]
+        );
+    }
+}
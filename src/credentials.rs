@@ -0,0 +1,352 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const SERVICE: &str = "gitai";
+const ACCOUNT: &str = "api_key";
+const CREDENTIALS_FILE: &str = "credentials";
+const CREDENTIALS_KEY_FILE: &str = "credentials.key";
+const NONCE_LEN: usize = 12;
+
+/// Somewhere to keep the API key other than the plain config file.
+pub trait CredentialStore {
+    /// Human readable location, shown after the key is saved.
+    fn location(&self) -> String;
+    fn get(&self) -> Result<Option<String>, Box<dyn std::error::Error>>;
+    fn set(&self, api_key: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// The desktop keyring, reached over D-Bus through `secret-tool`.
+pub struct SecretServiceStore;
+
+impl SecretServiceStore {
+    /// Whether there is a session bus and `secret-tool` can be run.
+    pub fn is_available() -> bool {
+        std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+            && Command::new("secret-tool")
+                .arg("--help")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok()
+    }
+}
+
+impl CredentialStore for SecretServiceStore {
+    fn location(&self) -> String {
+        "the Secret Service keyring".to_string()
+    }
+
+    fn get(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "service", SERVICE, "account", ACCOUNT])
+            .stderr(Stdio::null())
+            .output()?;
+
+        // `secret-tool lookup` also exits with 1 when nothing is stored
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let api_key = String::from_utf8(output.stdout)?.trim().to_string();
+        Ok(Some(api_key).filter(|key| !key.is_empty()))
+    }
+
+    fn set(&self, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label=gitai API key"])
+            .args(["service", SERVICE, "account", ACCOUNT])
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        // The secret goes through stdin so it never shows up in `ps`
+        child
+            .stdin
+            .take()
+            .ok_or("Failed to open secret-tool stdin")?
+            .write_all(api_key.as_bytes())?;
+
+        if !child.wait()?.success() {
+            return Err("Failed to store the API key with secret-tool".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Fallback for machines without a keyring: the key is encrypted with
+/// ChaCha20-Poly1305 under a random key kept in a separate file next to it.
+/// This is obfuscation, not protection: anyone who can read one file can read
+/// the other. It keeps the key out of the config file and out of a casual
+/// `cat` or `grep`, and both files are only readable by the owner.
+pub struct EncryptedFileStore {
+    config_dir: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(config_dir: &Path) -> Self {
+        Self {
+            config_dir: config_dir.to_path_buf(),
+        }
+    }
+
+    fn cipher(&self, create: bool) -> Result<Option<ChaCha20Poly1305>, Box<dyn std::error::Error>> {
+        let key_file = self.config_dir.join(CREDENTIALS_KEY_FILE);
+        if fs::exists(&key_file)? {
+            let key = fs::read(&key_file)?;
+            if key.len() != 32 {
+                return Err(format!("Invalid credentials key file {:?}", key_file).into());
+            }
+            return Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&key))));
+        }
+
+        if !create {
+            return Ok(None);
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&key_file, &key)?;
+        Ok(Some(ChaCha20Poly1305::new(&key)))
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn location(&self) -> String {
+        format!("{:?}", self.config_dir.join(CREDENTIALS_FILE))
+    }
+
+    fn get(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let credentials_file = self.config_dir.join(CREDENTIALS_FILE);
+        if !fs::exists(&credentials_file)? {
+            return Ok(None);
+        }
+        let Some(cipher) = self.cipher(false)? else {
+            return Err(format!("Missing {:?}", self.config_dir.join(CREDENTIALS_KEY_FILE)).into());
+        };
+
+        let invalid = || format!("Failed to decrypt {:?}", credentials_file);
+        let data = BASE64
+            .decode(fs::read_to_string(&credentials_file)?.trim())
+            .map_err(|_| invalid())?;
+        if data.len() < NONCE_LEN {
+            return Err(invalid().into());
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid())?;
+
+        Ok(Some(String::from_utf8(plaintext)?))
+    }
+
+    fn set(&self, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.config_dir)?;
+        let cipher = self
+            .cipher(true)?
+            .ok_or("Failed to create credentials key")?;
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, api_key.as_bytes())
+            .map_err(|_| "Failed to encrypt the API key")?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        write_private(
+            &self.config_dir.join(CREDENTIALS_FILE),
+            BASE64.encode(data).as_bytes(),
+        )
+    }
+}
+
+/// Saves to the first store and reads from the first one holding a key, so
+/// a key saved where the keyring was out of reach, such as over SSH, is still
+/// found once it is available, and the other way around.
+pub struct LayeredStore {
+    stores: Vec<Box<dyn CredentialStore>>,
+}
+
+impl LayeredStore {
+    pub fn new(stores: Vec<Box<dyn CredentialStore>>) -> Self {
+        Self { stores }
+    }
+}
+
+impl CredentialStore for LayeredStore {
+    fn location(&self) -> String {
+        self.stores
+            .first()
+            .map(|store| store.location())
+            .unwrap_or_default()
+    }
+
+    fn get(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let mut first_error = None;
+        for store in &self.stores {
+            match store.get() {
+                Ok(Some(api_key)) => return Ok(Some(api_key)),
+                Ok(None) => {}
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.stores
+            .first()
+            .ok_or("No credential store")?
+            .set(api_key)
+    }
+}
+
+/// The keyring when there is one, the encrypted file otherwise. Reads also
+/// look in the encrypted file when the keyring has no key.
+pub fn default_store(config_dir: &Path) -> Box<dyn CredentialStore> {
+    let file_store: Box<dyn CredentialStore> = Box::new(EncryptedFileStore::new(config_dir));
+    if SecretServiceStore::is_available() {
+        Box::new(LayeredStore::new(vec![
+            Box::new(SecretServiceStore),
+            file_store,
+        ]))
+    } else {
+        file_store
+    }
+}
+
+// Creates or replaces the file with owner-only permissions
+fn write_private(path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to newly created files
+        if fs::exists(path)? {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encrypted_file_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let store = EncryptedFileStore::new(temp_dir.path());
+
+        assert_eq!(store.get()?, None);
+
+        store.set("test-api-key-123")?;
+        assert_eq!(store.get()?.as_deref(), Some("test-api-key-123"));
+
+        store.set("test-api-key-456")?;
+        assert_eq!(store.get()?.as_deref(), Some("test-api-key-456"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_file_does_not_contain_key() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let store = EncryptedFileStore::new(temp_dir.path());
+
+        store.set("test-api-key-123")?;
+
+        let content = fs::read_to_string(temp_dir.path().join(CREDENTIALS_FILE))?;
+        assert!(!content.contains("test-api-key-123"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_encrypted_file_is_private() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        EncryptedFileStore::new(temp_dir.path()).set("test-api-key-123")?;
+
+        for file in [CREDENTIALS_FILE, CREDENTIALS_KEY_FILE] {
+            let mode = fs::metadata(temp_dir.path().join(file))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layered_store_reads_from_any_and_saves_to_first()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let keyring_dir = TempDir::new()?;
+        let file_dir = TempDir::new()?;
+        let layered = || {
+            LayeredStore::new(vec![
+                Box::new(EncryptedFileStore::new(keyring_dir.path())),
+                Box::new(EncryptedFileStore::new(file_dir.path())),
+            ])
+        };
+
+        assert_eq!(layered().get()?, None);
+
+        // Saved to the fallback while the first store was out of reach
+        EncryptedFileStore::new(file_dir.path()).set("test-api-key-123")?;
+        assert_eq!(layered().get()?.as_deref(), Some("test-api-key-123"));
+
+        layered().set("test-api-key-456")?;
+        assert_eq!(
+            EncryptedFileStore::new(keyring_dir.path())
+                .get()?
+                .as_deref(),
+            Some("test-api-key-456")
+        );
+        assert_eq!(layered().get()?.as_deref(), Some("test-api-key-456"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_file_tampered() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let store = EncryptedFileStore::new(temp_dir.path());
+        store.set("test-api-key-123")?;
+
+        let credentials_file = temp_dir.path().join(CREDENTIALS_FILE);
+        let mut data = BASE64.decode(fs::read_to_string(&credentials_file)?)?;
+        *data.last_mut().unwrap() ^= 1;
+        fs::write(&credentials_file, BASE64.encode(data))?;
+
+        let result = store.get();
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Failed to decrypt")
+        );
+
+        Ok(())
+    }
+}
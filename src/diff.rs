@@ -0,0 +1,740 @@
+use std::fmt;
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Serialize;
+
+/// Default budget for the diff part of the prompt.
+pub const MAX_DIFF_TOKENS: usize = 12_000;
+/// Most prompts spent summarizing a diff too large for a single one.
+pub const MAX_CHUNKS: usize = 16;
+// Rough average for code and English with the tokenizers we talk to
+const CHARS_PER_TOKEN: usize = 4;
+
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "Gemfile.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+];
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js", ".min.css", ".map", ".pb.go", "_pb2.py", ".g.dart", ".snap",
+];
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT", "auto-generated"];
+// Generators put their marker in a header comment at the top of the file
+const GENERATED_HEADER_LINES: usize = 10;
+const VENDORED_DIRS: &[&str] = &["vendor", "third_party", "node_modules"];
+const IGNORE_FILE: &str = ".gitaiignore";
+
+/// The part of a `git diff` output that belongs to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub text: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElisionReason {
+    Lockfile,
+    Generated,
+    Vendored,
+    WhitespaceOnly,
+    OverBudget,
+    Excluded,
+}
+
+/// A file whose changes were left out of the prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Elided {
+    pub path: String,
+    pub reason: ElisionReason,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// A diff with some files taken out, and what was taken out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimmedDiff {
+    pub diff: String,
+    pub elided: Vec<Elided>,
+}
+
+/// Files kept out of the prompt on purpose, listed in the repository's
+/// `.gitaiignore` and the `exclude` setting, both in gitignore syntax.
+pub struct Exclusions {
+    matcher: Gitignore,
+}
+
+impl Exclusions {
+    pub fn load(
+        repository_root: &Path,
+        patterns: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = GitignoreBuilder::new(repository_root);
+
+        let ignore_file = repository_root.join(IGNORE_FILE);
+        if ignore_file.exists()
+            && let Some(e) = builder.add(&ignore_file)
+        {
+            return Err(format!("Invalid ignore file {:?}: {}", ignore_file, e).into());
+        }
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| format!("Invalid exclude pattern '{}': {}", pattern, e))?;
+        }
+
+        Ok(Self {
+            matcher: builder.build()?,
+        })
+    }
+
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.matcher
+            .matched_path_or_any_parents(path, false)
+            .is_ignore()
+    }
+
+    /// Takes the excluded files out of the diff.
+    pub fn apply(&self, diff: &str) -> TrimmedDiff {
+        let mut kept = String::new();
+        let mut elided = Vec::new();
+
+        for file in split_files(diff) {
+            if self.is_excluded(&file.path) {
+                let reason = path_reason(&file.path).unwrap_or(ElisionReason::Excluded);
+                elided.push(Elided::new(&file, reason));
+            } else {
+                kept.push_str(&file.text);
+            }
+        }
+
+        TrimmedDiff { diff: kept, elided }
+    }
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Splits `git diff` output at each `diff --git` header.
+pub fn split_files(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    // `---`/`+++` are only headers before the first hunk of a file
+    let mut in_hunks = false;
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") || files.is_empty() {
+            files.push(FileDiff {
+                path: header_path(line),
+                text: String::new(),
+                additions: 0,
+                deletions: 0,
+            });
+            in_hunks = false;
+        }
+
+        let file = files.last_mut().expect("a file was just pushed");
+        file.text.push_str(line);
+
+        if line.starts_with("@@") {
+            in_hunks = true;
+        } else if !in_hunks {
+            if let Some(path) = line.strip_prefix("+++ b/") {
+                file.path = path.trim_end().to_string();
+            }
+        } else if line.starts_with('+') {
+            file.additions += 1;
+        } else if line.starts_with('-') {
+            file.deletions += 1;
+        }
+    }
+
+    files.retain(|file| !file.text.trim().is_empty());
+    files
+}
+
+/// Shrinks a diff that does not fit in `max_tokens`: lockfiles, generated
+/// or vendored files and whitespace-only changes are dropped first. What is
+/// left may take up to `max_chunks` prompts of `max_tokens` each to
+/// summarize, beyond that the largest files are dropped too. Dropped files
+/// are reported with their `--stat` line counts so the model still knows
+/// they changed.
+pub fn fit_to_budget(diff: &str, max_tokens: usize, max_chunks: usize) -> TrimmedDiff {
+    if estimate_tokens(diff) <= max_tokens {
+        return TrimmedDiff {
+            diff: diff.to_string(),
+            elided: Vec::new(),
+        };
+    }
+
+    let mut kept = Vec::new();
+    let mut elided = Vec::new();
+    for file in split_files(diff) {
+        match low_value_reason(&file) {
+            Some(reason) => elided.push(Elided::new(&file, reason)),
+            None => kept.push(file),
+        }
+    }
+
+    let max_total_tokens = max_tokens.saturating_mul(max_chunks);
+    let mut tokens: usize = kept.iter().map(|file| estimate_tokens(&file.text)).sum();
+    while tokens > max_total_tokens {
+        let Some(largest) = (0..kept.len()).max_by_key(|&i| kept[i].text.len()) else {
+            break;
+        };
+        let file = kept.remove(largest);
+        tokens -= estimate_tokens(&file.text);
+        elided.push(Elided::new(&file, ElisionReason::OverBudget));
+    }
+
+    TrimmedDiff {
+        diff: kept.into_iter().map(|file| file.text).collect(),
+        elided,
+    }
+}
+
+/// Splits the diff into pieces of at most about `max_tokens`, keeping files
+/// together where possible. Files too large for one piece are split between
+/// lines, and every piece repeats the file and hunk headers it belongs to.
+pub fn chunk(diff: &str, max_tokens: usize) -> Vec<String> {
+    let max_len = max_tokens.saturating_mul(CHARS_PER_TOKEN).max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for file in split_files(diff) {
+        let pieces = if file.text.len() > max_len {
+            split_file(&file.text, max_len)
+        } else {
+            vec![file.text]
+        };
+
+        for piece in pieces {
+            if !current.is_empty() && current.len() + piece.len() > max_len {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(&piece);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn split_file(text: &str, max_len: usize) -> Vec<String> {
+    let Some(body_start) = text.find("\n@@").map(|i| i + 1) else {
+        return vec![text.to_string()];
+    };
+    let (header, body) = text.split_at(body_start);
+    let max_body_len = max_len.saturating_sub(header.len()).max(1);
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut hunk_header = "";
+    for line in body.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            hunk_header = line;
+        }
+        if !current.is_empty() && current.len() + line.len() > max_body_len {
+            pieces.push(format!("{}{}", header, current));
+            current.clear();
+            if !line.starts_with("@@") {
+                current.push_str(hunk_header);
+            }
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        pieces.push(format!("{}{}", header, current));
+    }
+    pieces
+}
+
+/// Describes the elided files for the model, grouped by why they were left
+/// out: "2 lockfile changes omitted: Cargo.lock (+12 -3), yarn.lock (+40 -2)".
+pub fn omitted_notes(elided: &[Elided]) -> Vec<String> {
+    let mut groups: Vec<(ElisionReason, Vec<&Elided>)> = Vec::new();
+    for file in elided {
+        match groups.iter_mut().find(|(reason, _)| *reason == file.reason) {
+            Some((_, files)) => files.push(file),
+            None => groups.push((file.reason, vec![file])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(reason, files)| {
+            let noun = match reason {
+                ElisionReason::Lockfile => "lockfile change",
+                ElisionReason::Generated => "generated file change",
+                ElisionReason::Vendored => "vendored file change",
+                ElisionReason::WhitespaceOnly => "whitespace-only change",
+                ElisionReason::OverBudget => "large file change",
+                ElisionReason::Excluded => "excluded file change",
+            };
+            let list: Vec<String> = files
+                .iter()
+                .map(|file| format!("{} (+{} -{})", file.path, file.additions, file.deletions))
+                .collect();
+            format!(
+                "{} {}{} omitted: {}",
+                files.len(),
+                noun,
+                if files.len() == 1 { "" } else { "s" },
+                list.join(", ")
+            )
+        })
+        .collect()
+}
+
+impl Elided {
+    fn new(file: &FileDiff, reason: ElisionReason) -> Self {
+        Self {
+            path: file.path.clone(),
+            reason,
+            additions: file.additions,
+            deletions: file.deletions,
+        }
+    }
+}
+
+impl fmt::Display for ElisionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElisionReason::Lockfile => write!(f, "lockfile"),
+            ElisionReason::Generated => write!(f, "generated"),
+            ElisionReason::Vendored => write!(f, "vendored"),
+            ElisionReason::WhitespaceOnly => write!(f, "whitespace only"),
+            ElisionReason::OverBudget => write!(f, "too large"),
+            ElisionReason::Excluded => write!(f, "excluded"),
+        }
+    }
+}
+
+// Rendered like a `git diff --stat` line: "Cargo.lock | +12 -3 (lockfile)"
+impl fmt::Display for Elided {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} | +{} -{} ({})",
+            self.path, self.additions, self.deletions, self.reason
+        )
+    }
+}
+
+fn low_value_reason(file: &FileDiff) -> Option<ElisionReason> {
+    if let Some(reason) = path_reason(&file.path) {
+        Some(reason)
+    } else if header_lines(file).any(|line| GENERATED_MARKERS.iter().any(|m| line.contains(m))) {
+        Some(ElisionReason::Generated)
+    } else if is_whitespace_only(file) {
+        Some(ElisionReason::WhitespaceOnly)
+    } else {
+        None
+    }
+}
+
+// What the path alone says about a file
+fn path_reason(path: &str) -> Option<ElisionReason> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    if LOCKFILES.contains(&file_name) {
+        Some(ElisionReason::Lockfile)
+    } else if path.split('/').any(|dir| VENDORED_DIRS.contains(&dir)) {
+        Some(ElisionReason::Vendored)
+    } else if GENERATED_SUFFIXES
+        .iter()
+        .any(|suffix| file_name.ends_with(suffix))
+    {
+        Some(ElisionReason::Generated)
+    } else {
+        None
+    }
+}
+
+// Added and removed lines, including their `+`/`-` marker
+fn changed_lines(file: &FileDiff) -> impl Iterator<Item = &str> {
+    file.text
+        .lines()
+        .skip_while(|line| !line.starts_with("@@"))
+        .filter(|line| line.starts_with('+') || line.starts_with('-'))
+}
+
+// Lines of the diff that are among the first `GENERATED_HEADER_LINES` of
+// the file, before or after the change
+fn header_lines(file: &FileDiff) -> impl Iterator<Item = &str> {
+    let (mut old, mut new) = (usize::MAX, usize::MAX);
+    file.text
+        .lines()
+        .skip_while(|line| !line.starts_with("@@"))
+        .filter(move |line| {
+            if line.starts_with("@@") {
+                (old, new) = hunk_start(line).unwrap_or((usize::MAX, usize::MAX));
+                return false;
+            }
+            let number = match line.chars().next() {
+                Some('-') => &mut old,
+                Some('+') => &mut new,
+                Some(' ') => {
+                    old = old.saturating_add(1);
+                    &mut new
+                }
+                _ => return false,
+            };
+            let is_header = *number <= GENERATED_HEADER_LINES;
+            *number = number.saturating_add(1);
+            is_header
+        })
+}
+
+// "@@ -1,4 +1,5 @@ fn main()" -> (1, 1)
+fn hunk_start(line: &str) -> Option<(usize, usize)> {
+    let mut ranges = line.split(' ').skip(1);
+    let mut start = |sign: char| -> Option<usize> {
+        let range = ranges.next()?.strip_prefix(sign)?;
+        range.split(',').next()?.parse().ok()
+    };
+    Some((start('-')?, start('+')?))
+}
+
+// Whether the removed and added lines only differ in whitespace, including
+// re-wrapping
+fn is_whitespace_only(file: &FileDiff) -> bool {
+    let mut removed = String::new();
+    let mut added = String::new();
+    let mut changed = false;
+
+    for line in changed_lines(file) {
+        changed = true;
+        let (side, content) = match line.split_at(1) {
+            ("+", content) => (&mut added, content),
+            (_, content) => (&mut removed, content),
+        };
+        side.extend(content.chars().filter(|c| !c.is_whitespace()));
+    }
+
+    changed && removed == added
+}
+
+// "diff --git a/src/main.rs b/src/main.rs" -> "src/main.rs"
+fn header_path(line: &str) -> String {
+    line.trim_end()
+        .rsplit_once(" b/")
+        .map(|(_, path)| path.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff(path: &str, lines: &[&str]) -> String {
+        let mut diff = format!(
+            "diff --git a/{path} b/{path}\nindex 1234567..abcdefg 100644\n--- a/{path}\n+++ b/{path}\n@@ -1,{n} +1,{n} @@\n",
+            n = lines.len()
+        );
+        for line in lines {
+            diff.push_str(line);
+            diff.push('\n');
+        }
+        diff
+    }
+
+    #[test]
+    fn test_split_files() {
+        let diff = format!(
+            "{}{}",
+            file_diff("src/main.rs", &["-old", "+new", "+more", " same"]),
+            file_diff("README.md", &["+docs"])
+        );
+
+        let files = split_files(&diff);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert_eq!((files[0].additions, files[0].deletions), (2, 1));
+        assert_eq!(files[1].path, "README.md");
+        assert_eq!((files[1].additions, files[1].deletions), (1, 0));
+        assert_eq!(
+            files.iter().map(|f| f.text.as_str()).collect::<String>(),
+            diff
+        );
+    }
+
+    #[test]
+    fn test_split_files_deleted_file() {
+        let diff = "diff --git a/old.txt b/old.txt\ndeleted file mode 100644\n--- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\n";
+
+        let files = split_files(diff);
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "old.txt");
+        assert_eq!((files[0].additions, files[0].deletions), (0, 1));
+    }
+
+    #[test]
+    fn test_fit_to_budget_keeps_small_diff() {
+        let diff = format!(
+            "{}{}",
+            file_diff("Cargo.lock", &["+version = \"1.0.1\""]),
+            file_diff("src/main.rs", &["+fn main() {}"])
+        );
+
+        let budgeted = fit_to_budget(&diff, MAX_DIFF_TOKENS, 1);
+
+        assert_eq!(budgeted.diff, diff);
+        assert!(budgeted.elided.is_empty());
+    }
+
+    #[test]
+    fn test_fit_to_budget_drops_low_value_files_first() {
+        let lock_lines = vec!["+checksum = \"0123456789abcdef\""; 200];
+        let code = file_diff("src/main.rs", &["-let x = 1;", "+let x = 2;"]);
+        let diff = [
+            file_diff("Cargo.lock", &lock_lines),
+            code.clone(),
+            file_diff("src/schema.rs", &["+// @generated by diesel", "+table! {}"]),
+            file_diff("src/lib.rs", &["-fn a() {}", "+fn a() {", "+}"]),
+            file_diff("vendor/dep/lib.rs", &["+fn dep() {}"]),
+        ]
+        .concat();
+
+        let budgeted = fit_to_budget(&diff, 200, 1);
+
+        assert_eq!(budgeted.diff, code);
+        let reasons: Vec<_> = budgeted
+            .elided
+            .iter()
+            .map(|e| (e.path.as_str(), e.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("Cargo.lock", ElisionReason::Lockfile),
+                ("src/schema.rs", ElisionReason::Generated),
+                ("src/lib.rs", ElisionReason::WhitespaceOnly),
+                ("vendor/dep/lib.rs", ElisionReason::Vendored),
+            ]
+        );
+        assert_eq!(
+            budgeted.elided[0].to_string(),
+            "Cargo.lock | +200 -0 (lockfile)"
+        );
+    }
+
+    #[test]
+    fn test_generated_marker_only_counts_in_the_header() {
+        let generated = |text: &str| {
+            let file = split_files(text).remove(0);
+            low_value_reason(&file) == Some(ElisionReason::Generated)
+        };
+        let mut lines = vec![" let x = 1;"; 40];
+        lines.push("+// DO NOT EDIT the order of these entries");
+
+        assert!(generated(&file_diff(
+            "src/schema.rs",
+            &["+// @generated by diesel", "+table! {}"]
+        )));
+        assert!(!generated(&file_diff("src/routes.rs", &lines)));
+        assert!(generated(
+            "diff --git a/api.go b/api.go\n--- a/api.go\n+++ b/api.go\n@@ -1,3 +1,3 @@\n // Code generated by protoc. DO NOT EDIT.\n-var a = 1\n+var a = 2\n"
+        ));
+        assert!(!generated(
+            "diff --git a/api.go b/api.go\n--- a/api.go\n+++ b/api.go\n@@ -120,2 +120,2 @@\n-var a = 1\n+var a = 2 // auto-generated ids are fine\n"
+        ));
+    }
+
+    #[test]
+    fn test_fit_to_budget_drops_largest_files_when_still_over() {
+        let small = file_diff("src/small.rs", &["+fn small() {}"]);
+        let large_lines = vec!["+let value = compute_something_expensive();"; 100];
+        let medium_lines = vec!["+let value = 1;"; 30];
+        let diff = [
+            file_diff("src/large.rs", &large_lines),
+            small.clone(),
+            file_diff("src/medium.rs", &medium_lines),
+        ]
+        .concat();
+
+        let budgeted = fit_to_budget(&diff, estimate_tokens(&small), 1);
+
+        assert_eq!(budgeted.diff, small);
+        assert_eq!(
+            budgeted
+                .elided
+                .iter()
+                .map(|e| (e.path.as_str(), e.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                ("src/large.rs", ElisionReason::OverBudget),
+                ("src/medium.rs", ElisionReason::OverBudget),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_whitespace_only() {
+        let reindented = split_files(&file_diff(
+            "a.rs",
+            &["-  foo(a, b)", "+    foo(a,", "+        b)"],
+        ));
+        let changed = split_files(&file_diff("a.rs", &["-foo(a, b)", "+foo(a, c)"]));
+        let mode_change =
+            split_files("diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n");
+
+        assert!(is_whitespace_only(&reindented[0]));
+        assert!(!is_whitespace_only(&changed[0]));
+        assert!(!is_whitespace_only(&mode_change[0]));
+    }
+
+    #[test]
+    fn test_fit_to_budget_keeps_what_fits_in_max_chunks() {
+        let lines = vec!["+let value = compute_something_expensive();"; 20];
+        let diff = [file_diff("src/a.rs", &lines), file_diff("src/b.rs", &lines)].concat();
+        let file_tokens = estimate_tokens(&file_diff("src/a.rs", &lines));
+
+        let budgeted = fit_to_budget(&diff, file_tokens, 2);
+
+        assert_eq!(budgeted.diff, diff);
+        assert!(budgeted.elided.is_empty());
+    }
+
+    #[test]
+    fn test_chunk_groups_whole_files() {
+        let a = file_diff("src/a.rs", &["+fn a() {}"]);
+        let b = file_diff("src/b.rs", &["+fn b() {}"]);
+        let c = file_diff("src/c.rs", &["+fn c() {}"]);
+        let diff = [a.clone(), b.clone(), c.clone()].concat();
+
+        assert_eq!(chunk(&diff, MAX_DIFF_TOKENS), vec![diff.clone()]);
+        assert_eq!(
+            chunk(&diff, estimate_tokens(&a) * 2),
+            vec![format!("{}{}", a, b), c]
+        );
+    }
+
+    #[test]
+    fn test_chunk_splits_large_file_with_headers() {
+        let lines: Vec<String> = (0..100)
+            .map(|i| format!("+let line_{} = {};", i, i))
+            .collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let diff = file_diff("src/big.rs", &lines);
+
+        let chunks = chunk(&diff, 100);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("diff --git a/src/big.rs b/src/big.rs\n"));
+            assert!(chunk.contains("\n@@ -1,100 +1,100 @@\n"));
+            assert!(estimate_tokens(chunk) <= 100, "{}", estimate_tokens(chunk));
+        }
+        let added: usize = chunks.iter().map(|c| split_files(c)[0].additions).sum();
+        assert_eq!(added, 100);
+    }
+
+    #[test]
+    fn test_exclusions_from_ignore_file_and_patterns() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join(IGNORE_FILE),
+            "# snapshots\n*.snap\n/docs/\n!docs/keep.md\n",
+        )?;
+
+        let exclusions = Exclusions::load(temp_dir.path(), &["Cargo.lock".to_string()])?;
+
+        assert!(exclusions.is_excluded("Cargo.lock"));
+        assert!(exclusions.is_excluded("crates/core/Cargo.lock"));
+        assert!(exclusions.is_excluded("tests/snapshots/parse.snap"));
+        assert!(exclusions.is_excluded("docs/guide.md"));
+        assert!(!exclusions.is_excluded("docs/keep.md"));
+        assert!(!exclusions.is_excluded("src/docs/guide.md"));
+        assert!(!exclusions.is_excluded("src/main.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclusions_without_ignore_file() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let exclusions = Exclusions::load(temp_dir.path(), &[])?;
+
+        assert!(!exclusions.is_excluded("Cargo.lock"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclusions_apply() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let code = file_diff("src/main.rs", &["+fn main() {}"]);
+        let diff = [
+            file_diff("Cargo.lock", &["-old", "+new"]),
+            code.clone(),
+            file_diff("fixtures/data.json", &["+{}"]),
+        ]
+        .concat();
+
+        let exclusions = Exclusions::load(
+            temp_dir.path(),
+            &["Cargo.lock".to_string(), "fixtures/".to_string()],
+        )?;
+        let trimmed = exclusions.apply(&diff);
+
+        assert_eq!(trimmed.diff, code);
+        assert_eq!(
+            trimmed
+                .elided
+                .iter()
+                .map(|e| (e.path.as_str(), e.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Cargo.lock", ElisionReason::Lockfile),
+                ("fixtures/data.json", ElisionReason::Excluded),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_omitted_notes_groups_by_reason() {
+        let elided = |path: &str, reason| Elided {
+            path: path.to_string(),
+            reason,
+            additions: 2,
+            deletions: 1,
+        };
+
+        let notes = omitted_notes(&[
+            elided("Cargo.lock", ElisionReason::Lockfile),
+            elided("src/big.rs", ElisionReason::OverBudget),
+            elided("web/package-lock.json", ElisionReason::Lockfile),
+        ]);
+
+        assert_eq!(
+            notes,
+            vec![
+                "2 lockfile changes omitted: Cargo.lock (+2 -1), web/package-lock.json (+2 -1)",
+                "1 large file change omitted: src/big.rs (+2 -1)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Failures talking to an LLM provider.
+#[derive(Debug)]
+pub enum Error {
+    /// The API key is missing, invalid or lacks permission (401, 403).
+    Authentication(ApiError),
+    /// Too many requests (429).
+    RateLimit(ApiError),
+    /// The API is temporarily over capacity (529, 503).
+    Overloaded(ApiError),
+    /// The request itself was rejected, e.g. unknown model (400, 404, 413, 422).
+    InvalidRequest(ApiError),
+    /// Any other error reported by the API.
+    Api(ApiError),
+    /// The API could not be reached or the connection dropped.
+    Network(String),
+    /// The request did not complete in time.
+    Timeout,
+    /// The response could not be understood.
+    Decode(String),
+}
+
+/// Details of an error reported by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ApiError {
+    /// HTTP status, absent for errors delivered inside a stream.
+    pub status: Option<u16>,
+    pub request_id: Option<String>,
+    pub error_type: Option<String>,
+    pub message: String,
+    /// How long the API asked us to wait before trying again.
+    pub retry_after: Option<Duration>,
+}
+
+// Both the Anthropic and the OpenAI APIs wrap failures as
+// `{"error": {"message": ..., "type": ...}}`, Ollama as `{"error": "..."}`
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorBody {
+    Structured(ErrorResponse),
+    Plain(String),
+}
+
+#[derive(Deserialize)]
+struct ErrorWrapper {
+    error: ErrorBody,
+}
+
+impl Error {
+    /// Builds the error for a non-success HTTP response.
+    pub fn from_response(status: u16, request_id: Option<String>, body: &str) -> Self {
+        let (error_type, message) = match serde_json::from_str::<ErrorWrapper>(body) {
+            Ok(ErrorWrapper {
+                error: ErrorBody::Structured(error),
+            }) => (error.error_type, error.message),
+            Ok(ErrorWrapper {
+                error: ErrorBody::Plain(message),
+            }) => (None, message),
+            Err(_) => (None, body.trim().to_string()),
+        };
+
+        Self::from_api_error(ApiError {
+            status: Some(status),
+            request_id,
+            error_type,
+            message,
+            retry_after: None,
+        })
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        if let Some(api_error) = self.api_error_mut() {
+            api_error.retry_after = retry_after;
+        }
+        self
+    }
+
+    /// Classifies an API error by HTTP status, falling back to the error
+    /// type for errors that arrive without one (e.g. mid-stream).
+    pub fn from_api_error(api_error: ApiError) -> Self {
+        match (api_error.status, api_error.error_type.as_deref()) {
+            (Some(401 | 403), _) => Error::Authentication(api_error),
+            (Some(429), _) => Error::RateLimit(api_error),
+            (Some(503 | 529), _) => Error::Overloaded(api_error),
+            (Some(400 | 404 | 413 | 422), _) => Error::InvalidRequest(api_error),
+            (Some(_), _) => Error::Api(api_error),
+            (None, Some("authentication_error" | "permission_error")) => {
+                Error::Authentication(api_error)
+            }
+            (None, Some("rate_limit_error")) => Error::RateLimit(api_error),
+            (None, Some("overloaded_error")) => Error::Overloaded(api_error),
+            (None, Some("invalid_request_error" | "not_found_error" | "request_too_large")) => {
+                Error::InvalidRequest(api_error)
+            }
+            (None, _) => Error::Api(api_error),
+        }
+    }
+
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Error::Authentication(api_error)
+            | Error::RateLimit(api_error)
+            | Error::Overloaded(api_error)
+            | Error::InvalidRequest(api_error)
+            | Error::Api(api_error) => Some(api_error),
+            Error::Network(_) | Error::Timeout | Error::Decode(_) => None,
+        }
+    }
+
+    fn api_error_mut(&mut self) -> Option<&mut ApiError> {
+        match self {
+            Error::Authentication(api_error)
+            | Error::RateLimit(api_error)
+            | Error::Overloaded(api_error)
+            | Error::InvalidRequest(api_error)
+            | Error::Api(api_error) => Some(api_error),
+            Error::Network(_) | Error::Timeout | Error::Decode(_) => None,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RateLimit(_) | Error::Overloaded(_) | Error::Network(_) => true,
+            Error::Api(api_error) => api_error.status.is_none_or(|status| status >= 500),
+            // Each attempt already waited the whole request timeout, a few
+            // more would keep the user waiting for minutes
+            Error::Timeout => false,
+            Error::Authentication(_) | Error::InvalidRequest(_) | Error::Decode(_) => false,
+        }
+    }
+
+    /// What the user can do about it, if anything.
+    pub fn advice(&self) -> Option<&'static str> {
+        match self {
+            Error::Authentication(_) => {
+                Some("Check your API key, or run 'gitai init' to configure a new one.")
+            }
+            Error::RateLimit(_) => Some("You are being rate limited. Wait a moment and try again."),
+            Error::Overloaded(_) => Some("The API is temporarily overloaded. Try again shortly."),
+            Error::InvalidRequest(_) => {
+                Some("Check the configured model name, or stage a smaller set of changes.")
+            }
+            Error::Network(_) => {
+                Some("Check your network connection and the configured API base URL.")
+            }
+            Error::Timeout => Some("The API took too long to respond. Try again shortly."),
+            Error::Api(_) | Error::Decode(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Authentication(api_error) => write!(f, "Authentication failed{}", api_error),
+            Error::RateLimit(api_error) => write!(f, "Rate limit exceeded{}", api_error),
+            Error::Overloaded(api_error) => write!(f, "API overloaded{}", api_error),
+            Error::InvalidRequest(api_error) => write!(f, "Invalid request{}", api_error),
+            Error::Api(api_error) => write!(f, "API error{}", api_error),
+            Error::Network(message) => write!(f, "Network error: {}", message),
+            Error::Timeout => write!(f, "Request timed out"),
+            Error::Decode(message) => write!(f, "Failed to decode response: {}", message),
+        }
+    }
+}
+
+// Rendered as the suffix of `Error`'s message: " (HTTP 401, request req_1): invalid x-api-key"
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details: Vec<String> = [
+            self.status.map(|status| format!("HTTP {}", status)),
+            self.request_id
+                .as_ref()
+                .map(|request_id| format!("request {}", request_id)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Error::Timeout
+        } else if error.is_decode() {
+            Error::Decode(error.to_string())
+        } else {
+            Error::Network(error.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Decode(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_response_anthropic_authentication() {
+        let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+
+        let error = Error::from_response(401, Some("req_123".to_string()), body);
+
+        assert!(matches!(error, Error::Authentication(_)));
+        assert_eq!(
+            error.api_error(),
+            Some(&ApiError {
+                status: Some(401),
+                request_id: Some("req_123".to_string()),
+                error_type: Some("authentication_error".to_string()),
+                message: "invalid x-api-key".to_string(),
+                retry_after: None,
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Authentication failed (HTTP 401, request req_123): invalid x-api-key"
+        );
+        assert!(error.advice().unwrap().contains("gitai init"));
+    }
+
+    #[test]
+    fn test_from_response_classifies_by_status() {
+        let body = r#"{"error":{"message":"slow down","type":null}}"#;
+
+        assert!(matches!(
+            Error::from_response(429, None, body),
+            Error::RateLimit(_)
+        ));
+        assert!(matches!(
+            Error::from_response(529, None, body),
+            Error::Overloaded(_)
+        ));
+        assert!(matches!(
+            Error::from_response(400, None, body),
+            Error::InvalidRequest(_)
+        ));
+        assert!(matches!(
+            Error::from_response(500, None, body),
+            Error::Api(_)
+        ));
+    }
+
+    #[test]
+    fn test_from_response_ollama_plain_error() {
+        let body = r#"{"error":"model 'llama9' not found, try pulling it first"}"#;
+
+        let error = Error::from_response(404, None, body);
+
+        assert!(matches!(error, Error::InvalidRequest(_)));
+        assert_eq!(
+            error.to_string(),
+            "Invalid request (HTTP 404): model 'llama9' not found, try pulling it first"
+        );
+    }
+
+    #[test]
+    fn test_from_response_non_json_body() {
+        let error = Error::from_response(502, None, "upstream unavailable\n");
+
+        assert_eq!(
+            error.to_string(),
+            "API error (HTTP 502): upstream unavailable"
+        );
+    }
+
+    #[test]
+    fn test_from_api_error_without_status_uses_error_type() {
+        let error = Error::from_api_error(ApiError {
+            error_type: Some("overloaded_error".to_string()),
+            message: "Overloaded".to_string(),
+            ..ApiError::default()
+        });
+
+        assert!(matches!(error, Error::Overloaded(_)));
+        assert_eq!(error.to_string(), "API overloaded: Overloaded");
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let body = r#"{"error":{"message":"oops","type":null}}"#;
+
+        assert!(Error::from_response(429, None, body).is_retryable());
+        assert!(Error::from_response(529, None, body).is_retryable());
+        assert!(Error::from_response(500, None, body).is_retryable());
+        assert!(!Error::Timeout.is_retryable());
+        assert!(!Error::from_response(401, None, body).is_retryable());
+        assert!(!Error::from_response(400, None, body).is_retryable());
+        assert!(!Error::Decode("oops".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_with_retry_after() {
+        let retry_after = Some(Duration::from_secs(7));
+        let body = r#"{"error":{"message":"slow down","type":"rate_limit_error"}}"#;
+
+        let error = Error::from_response(429, None, body).with_retry_after(retry_after);
+        assert_eq!(error.api_error().unwrap().retry_after, retry_after);
+
+        let error = Error::Timeout.with_retry_after(retry_after);
+        assert!(error.api_error().is_none());
+    }
+
+    #[test]
+    fn test_decode_error_from_serde() {
+        let error: Error = serde_json::from_str::<serde_json::Value>("invalid json")
+            .unwrap_err()
+            .into();
+
+        assert!(matches!(error, Error::Decode(_)));
+        assert!(error.to_string().contains("expected value at line"));
+    }
+}
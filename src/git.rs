@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Runs git in `path`, returning its output or its error message.
+pub fn git_at(path: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").current_dir(path).args(args).output()?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+pub fn rev_list_at(path: &Path, args: &[&str]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = git_at(path, &[&["rev-list"], args].concat())?;
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+/// What starts a comment line in commit message files, `core.commentString`
+/// or `core.commentChar`. With `auto` git picks a character per message,
+/// which cannot be known afterwards, so the default `#` is assumed.
+pub fn comment_string_at(path: &Path) -> String {
+    ["core.commentString", "core.commentChar"]
+        .iter()
+        .find_map(|key| git_at(path, &["config", "--get", key]).ok())
+        .map(|value| value.trim_end_matches('\n').to_string())
+        .filter(|value| !value.is_empty() && value != "auto")
+        .unwrap_or_else(|| "#".to_string())
+}
+
+/// The full messages of the commits in `range` apart from merges, oldest
+/// first.
+pub fn messages_at(path: &Path, range: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = git_at(
+        path,
+        &["log", "--reverse", "--no-merges", "--format=%B%x00", range],
+    )?;
+    Ok(output
+        .split('\0')
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_messages_in_range() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        git_at(repo, &["init", "-q"])?;
+        for message in ["Initial", "Add parser\n\nWith a body.", "Add tests"] {
+            git_at(
+                repo,
+                &[
+                    "-c",
+                    "user.name=Test",
+                    "-c",
+                    "user.email=test@example.com",
+                    "commit",
+                    "-q",
+                    "--allow-empty",
+                    "-m",
+                    message,
+                ],
+            )?;
+        }
+
+        assert_eq!(
+            messages_at(repo, "HEAD~2..")?,
+            vec!["Add parser\n\nWith a body.", "Add tests"]
+        );
+        assert_eq!(rev_list_at(repo, &["HEAD"])?.len(), 3);
+        assert!(git_at(repo, &["rev-parse", "missing"]).is_err());
+
+        assert_eq!(comment_string_at(repo), "#");
+        git_at(repo, &["config", "core.commentChar", ";"])?;
+        assert_eq!(comment_string_at(repo), ";");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Identifies hooks written by gitai, so others are never overwritten or removed
+const MARKER: &str = "# Installed by gitai";
+
+/// The hooks gitai can install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Generates the message for a plain `git commit`.
+    PrepareCommitMsg,
+    /// Lints the message before the commit is made.
+    CommitMsg,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PrepareCommitMsg => "prepare-commit-msg",
+            Hook::CommitMsg => "commit-msg",
+        }
+    }
+
+    // The gitai command the hook runs, given the arguments git passes to it
+    fn command(self) -> &'static str {
+        match self {
+            Hook::PrepareCommitMsg => "hook run",
+            Hook::CommitMsg => "lint --hook",
+        }
+    }
+}
+
+/// The directory git runs hooks from, honoring `core.hooksPath`.
+pub fn hooks_dir_at(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .current_dir(path)
+        .arg("rev-parse")
+        .arg("--git-path")
+        .arg("hooks")
+        .output()?;
+
+    if !output.status.success() {
+        return Err("Failed to find the git hooks directory".into());
+    }
+
+    // Relative to `path`, unless `core.hooksPath` is absolute
+    let hooks_dir = String::from_utf8(output.stdout)?;
+    Ok(path.join(hooks_dir.trim()))
+}
+
+/// Writes the hook, replacing an earlier gitai hook but never one installed
+/// by something else.
+pub fn install(
+    hooks_dir: &Path,
+    hook: Hook,
+    gitai: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let hook_file = hooks_dir.join(hook.name());
+    if fs::exists(&hook_file)? && !is_gitai_hook(&hook_file)? {
+        return Err(format!(
+            "A {} hook already exists at {:?}. Remove it or call 'gitai {} \"$@\"' from it.",
+            hook.name(),
+            hook_file,
+            hook.command()
+        )
+        .into());
+    }
+
+    fs::create_dir_all(hooks_dir)?;
+    let script = format!(
+        "#!/bin/sh\n{}, remove with 'gitai hook uninstall{}'\nexec '{}' {} \"$@\"\n",
+        MARKER,
+        match hook {
+            Hook::PrepareCommitMsg => "",
+            Hook::CommitMsg => " --lint",
+        },
+        gitai.display().to_string().replace('\'', r"'\''"),
+        hook.command()
+    );
+    fs::write(&hook_file, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_file, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(hook_file)
+}
+
+pub fn uninstall(hooks_dir: &Path, hook: Hook) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let hook_file = hooks_dir.join(hook.name());
+    if !fs::exists(&hook_file)? {
+        return Err(format!("No {} hook found at {:?}", hook.name(), hook_file).into());
+    }
+    if !is_gitai_hook(&hook_file)? {
+        return Err(format!(
+            "The {} hook at {:?} was not installed by gitai, leaving it alone",
+            hook.name(),
+            hook_file
+        )
+        .into());
+    }
+
+    fs::remove_file(&hook_file)?;
+    Ok(hook_file)
+}
+
+/// Whether a message should be generated for the commit source git passes
+/// to the hook. Messages given with `-m`/`-F`, templates, merges, squashes
+/// and amends are left alone; only a plain `git commit` gets one.
+pub fn should_generate(source: Option<&str>) -> bool {
+    source.is_none_or(str::is_empty)
+}
+
+/// Puts the message above what git prepared, usually its help comments.
+pub fn write_message(message_file: &Path, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let existing = fs::read_to_string(message_file).unwrap_or_default();
+    fs::write(
+        message_file,
+        format!("{}\n{}", message.trim_end(), existing),
+    )?;
+
+    Ok(())
+}
+
+fn is_gitai_hook(hook_file: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(fs::read_to_string(hook_file)?.contains(MARKER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let status = Command::new("git").current_dir(dir).args(args).status()?;
+        assert!(status.success(), "git {:?} failed", args);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hooks_dir_default_and_hooks_path() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"])?;
+
+        assert_eq!(hooks_dir_at(repo)?, repo.join(".git/hooks"));
+
+        git(repo, &["config", "core.hooksPath", ".githooks"])?;
+        assert_eq!(hooks_dir_at(repo)?, repo.join(".githooks"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_and_uninstall() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let hooks_dir = temp_dir.path().join("hooks");
+
+        let hook_file = install(
+            &hooks_dir,
+            Hook::PrepareCommitMsg,
+            Path::new("/usr/local/bin/gitai"),
+        )?;
+
+        let script = fs::read_to_string(&hook_file)?;
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("exec '/usr/local/bin/gitai' hook run \"$@\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&hook_file)?.permissions().mode() & 0o777,
+                0o755
+            );
+        }
+
+        // Reinstalling replaces our own hook
+        install(&hooks_dir, Hook::PrepareCommitMsg, Path::new("/opt/gitai"))?;
+        assert!(fs::read_to_string(&hook_file)?.contains("exec '/opt/gitai'"));
+
+        // The commit-msg hook sits next to it
+        let lint_hook_file = install(&hooks_dir, Hook::CommitMsg, Path::new("/opt/gitai"))?;
+        assert_eq!(lint_hook_file, hooks_dir.join("commit-msg"));
+        assert!(
+            fs::read_to_string(&lint_hook_file)?.contains("exec '/opt/gitai' lint --hook \"$@\"")
+        );
+
+        uninstall(&hooks_dir, Hook::PrepareCommitMsg)?;
+        assert!(!hook_file.exists());
+        assert!(lint_hook_file.exists());
+        assert!(uninstall(&hooks_dir, Hook::PrepareCommitMsg).is_err());
+        uninstall(&hooks_dir, Hook::CommitMsg)?;
+        assert!(!lint_hook_file.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_hook_is_left_alone() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let hook_file = temp_dir.path().join(Hook::PrepareCommitMsg.name());
+        fs::write(&hook_file, "#!/bin/sh\necho custom\n")?;
+
+        assert!(install(temp_dir.path(), Hook::PrepareCommitMsg, Path::new("gitai")).is_err());
+        assert!(uninstall(temp_dir.path(), Hook::PrepareCommitMsg).is_err());
+        assert_eq!(fs::read_to_string(&hook_file)?, "#!/bin/sh\necho custom\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_generate() {
+        assert!(should_generate(None));
+        assert!(should_generate(Some("")));
+        for source in ["message", "template", "merge", "squash", "commit"] {
+            assert!(!should_generate(Some(source)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_write_message_keeps_git_comments() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let message_file = temp_dir.path().join("COMMIT_EDITMSG");
+        fs::write(&message_file, "\n# Please enter the commit message\n")?;
+
+        write_message(&message_file, "Add login form\n\n")?;
+
+        assert_eq!(
+            fs::read_to_string(&message_file)?,
+            "Add login form\n\n# Please enter the commit message\n"
+        );
+
+        Ok(())
+    }
+}
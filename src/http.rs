@@ -0,0 +1,313 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::error::{ApiError, Error};
+
+pub type ChunkCallback<'a> = dyn FnMut(&[u8]) + Send + 'a;
+
+#[async_trait::async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn post_json<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+    ) -> Result<String, Error>;
+
+    /// Like `post_json`, but hands the response body to `on_chunk` piece by
+    /// piece as it arrives. Clients that cannot stream deliver it in one go.
+    async fn post_json_stream<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<(), Error> {
+        let response = self.post_json(url, headers, body).await?;
+        on_chunk(response.as_bytes());
+        Ok(())
+    }
+}
+
+pub struct ReqwestHttpClient {
+    client: reqwest::Client,
+}
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+impl ReqwestHttpClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+        }
+    }
+}
+
+impl Default for ReqwestHttpClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Response headers carrying the request id, by provider
+const REQUEST_ID_HEADERS: [&str; 2] = ["request-id", "x-request-id"];
+
+fn build_header_map(headers: Vec<(String, String)>) -> Result<reqwest::header::HeaderMap, Error> {
+    let mut header_map = reqwest::header::HeaderMap::new();
+    for (key, value) in headers {
+        let invalid_header = |e: &dyn std::fmt::Display| {
+            Error::InvalidRequest(ApiError {
+                message: format!("Invalid value for header '{}': {}", key, e),
+                ..ApiError::default()
+            })
+        };
+        header_map.insert(
+            reqwest::header::HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| invalid_header(&e))?,
+            reqwest::header::HeaderValue::from_str(&value).map_err(|e| invalid_header(&e))?,
+        );
+    }
+
+    Ok(header_map)
+}
+
+fn request_id(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_string)
+}
+
+// Anthropic reports, per limit, how much is left and when it resets
+const RATE_LIMIT_HEADERS: [(&str, &str); 4] = [
+    (
+        "anthropic-ratelimit-requests-remaining",
+        "anthropic-ratelimit-requests-reset",
+    ),
+    (
+        "anthropic-ratelimit-tokens-remaining",
+        "anthropic-ratelimit-tokens-reset",
+    ),
+    (
+        "anthropic-ratelimit-input-tokens-remaining",
+        "anthropic-ratelimit-input-tokens-reset",
+    ),
+    (
+        "anthropic-ratelimit-output-tokens-remaining",
+        "anthropic-ratelimit-output-tokens-reset",
+    ),
+];
+
+/// How long the server asked us to wait: `retry-after-ms` (OpenAI),
+/// `retry-after` in seconds, or else the reset time of whichever Anthropic
+/// rate limit is exhausted.
+fn retry_after(headers: &reqwest::header::HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(millis / 1000.0).ok();
+    }
+    if let Some(seconds) = header("retry-after").and_then(|value| value.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+
+    RATE_LIMIT_HEADERS
+        .iter()
+        .filter(|(remaining, _)| header(remaining) == Some("0"))
+        .filter_map(|(_, reset)| parse_rfc3339(header(reset)?))
+        .map(|reset| reset.duration_since(now).unwrap_or(Duration::ZERO))
+        .max()
+}
+
+/// Parses timestamps like `2024-07-22T20:33:28Z` or
+/// `2024-07-22T22:33:28.5+02:00`.
+fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    let (date, time) = value.split_once(['T', 't', ' '])?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    let (time, offset_seconds) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let sign_index = time.rfind(['+', '-'])?;
+        let (time, offset) = time.split_at(sign_index);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        (time, sign * offset)
+    };
+
+    let mut time_parts = time.splitn(3, ':');
+    let hour: i64 = time_parts.next()?.parse().ok()?;
+    let minute: i64 = time_parts.next()?.parse().ok()?;
+    let second: f64 = time_parts.next()?.parse().ok()?;
+
+    // Days since the epoch, from http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 - offset_seconds;
+    let timestamp = seconds as f64 + second;
+    if timestamp < 0.0 {
+        return None;
+    }
+
+    Some(UNIX_EPOCH + Duration::from_secs_f64(timestamp))
+}
+
+impl ReqwestHttpClient {
+    async fn send<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+    ) -> Result<reqwest::Response, Error> {
+        let header_map = build_header_map(headers)?;
+        let response = self
+            .client
+            .post(url)
+            .headers(header_map)
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let retry_after = retry_after(response.headers(), SystemTime::now());
+            let response_text = response.text().await?;
+            return Err(
+                Error::from_response(status.as_u16(), request_id, &response_text)
+                    .with_retry_after(retry_after),
+            );
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpClient for ReqwestHttpClient {
+    async fn post_json<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+    ) -> Result<String, Error> {
+        let response = self.send(url, headers, body).await?;
+        let response_text = response.text().await?;
+
+        Ok(response_text)
+    }
+
+    async fn post_json_stream<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<(), Error> {
+        let mut response = self.send(url, headers, body).await?;
+
+        while let Some(chunk) = response.chunk().await? {
+            on_chunk(&chunk);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    #[test]
+    fn test_request_id() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_id(&headers), None);
+
+        headers.insert("x-request-id", HeaderValue::from_static("req_openai"));
+        assert_eq!(request_id(&headers).as_deref(), Some("req_openai"));
+
+        headers.insert("request-id", HeaderValue::from_static("req_anthropic"));
+        assert_eq!(request_id(&headers).as_deref(), Some("req_anthropic"));
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let now = SystemTime::now();
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("12"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(12)));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(
+            retry_after(&headers, now),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn test_retry_after_exhausted_rate_limit_reset() {
+        let now = parse_rfc3339("2024-07-22T20:33:00Z").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "anthropic-ratelimit-requests-remaining",
+            HeaderValue::from_static("12"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-requests-reset",
+            HeaderValue::from_static("2024-07-22T20:34:00Z"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-remaining",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("2024-07-22T20:33:20Z"),
+        );
+
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse_rfc3339("2024-07-22T20:33:28Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(1721680408))
+        );
+        assert_eq!(
+            parse_rfc3339("2024-07-22T22:33:28.5+02:00"),
+            Some(UNIX_EPOCH + Duration::from_millis(1721680408500))
+        );
+        assert_eq!(parse_rfc3339("next tuesday"), None);
+    }
+
+    #[test]
+    fn test_build_header_map_invalid_value() {
+        let result = build_header_map(vec![(
+            "x-api-key".to_string(),
+            "sk-ant\nmalformed".to_string(),
+        )]);
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, Error::InvalidRequest(_)));
+        assert!(error.to_string().contains("x-api-key"));
+    }
+}
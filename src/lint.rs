@@ -0,0 +1,507 @@
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::conventional::{ConventionalCommit, TYPES};
+
+const SUBJECT_LIMIT: usize = 50;
+const BODY_LIMIT: usize = 72;
+// Git cuts the message here when `git commit --verbose` adds the diff below,
+// after the comment string
+const SCISSORS: &str = " ------------------------ >8 ------------------------";
+// Subjects git writes itself, or that `git rebase --autosquash` folds away
+const EXEMPT_PREFIXES: &[&str] = &["fixup! ", "squash! ", "amend! ", "Merge ", "Revert \""];
+
+// Verbs subjects often start with, to tell "Added" or "Adds" from "Add"
+const VERBS: &[&str] = &[
+    "add",
+    "allow",
+    "bump",
+    "change",
+    "clean",
+    "convert",
+    "create",
+    "delete",
+    "deprecate",
+    "disable",
+    "document",
+    "drop",
+    "enable",
+    "ensure",
+    "extract",
+    "fix",
+    "handle",
+    "implement",
+    "improve",
+    "introduce",
+    "make",
+    "merge",
+    "move",
+    "optimize",
+    "prevent",
+    "refactor",
+    "remove",
+    "rename",
+    "replace",
+    "restore",
+    "revert",
+    "show",
+    "simplify",
+    "split",
+    "support",
+    "switch",
+    "update",
+    "upgrade",
+    "use",
+    "validate",
+    "write",
+];
+
+static BREAKING_FOOTER_LIKE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^breaking[ _-]?changes?\s*:").unwrap());
+static BREAKING_FOOTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^BREAKING[ -]CHANGE: \S").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Breaks a rule, failing the lint.
+    Error,
+    /// Probably breaks a rule, found by a heuristic.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A rule the message breaks, with the 1-based line it breaks it on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.severity, self.message)
+    }
+}
+
+impl Problem {
+    fn error(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn warning(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Which rules apply besides `GIT_COMMIT_BEST_PRACTICES`.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    /// Require conventional commits.
+    pub conventional: bool,
+    /// Conventional commit scopes the project allows, any when empty.
+    pub scopes: Vec<String>,
+}
+
+/// Checks a message against `GIT_COMMIT_BEST_PRACTICES` and, in conventional
+/// mode or when it already is a conventional commit,
+/// `CONVENTIONAL_COMMIT_ADDITIONAL_RULES`.
+pub fn lint(message: &str, rules: &Rules) -> Vec<Problem> {
+    let lines: Vec<&str> = message.trim_end().lines().collect();
+    let Some(subject) = lines.first().filter(|subject| !subject.trim().is_empty()) else {
+        return vec![Problem::error(1, "The message is empty")];
+    };
+
+    let mut problems = Vec::new();
+    let length = subject.chars().count();
+    if length > SUBJECT_LIMIT {
+        problems.push(Problem::error(
+            1,
+            format!(
+                "The subject is {} characters long, keep it to {} or less",
+                length, SUBJECT_LIMIT
+            ),
+        ));
+    }
+    if lines.get(1).is_some_and(|line| !line.trim().is_empty()) {
+        problems.push(Problem::error(
+            2,
+            "Separate the subject from the body with a blank line",
+        ));
+    }
+    for (index, line) in lines.iter().enumerate().skip(2) {
+        let length = line.chars().count();
+        // A single word, such as a URL, cannot be wrapped
+        let words = line
+            .split_whitespace()
+            .filter(|word| !["-", "*"].contains(word))
+            .count();
+        if length > BODY_LIMIT && words > 1 {
+            problems.push(Problem::error(
+                index + 1,
+                format!(
+                    "The line is {} characters long, wrap the body at {}",
+                    length, BODY_LIMIT
+                ),
+            ));
+        }
+    }
+
+    let is_conventional =
+        ConventionalCommit::parse(message).is_some_and(|commit| commit.has_known_type());
+    if rules.conventional || is_conventional {
+        problems.extend(lint_conventional(message, &lines, &rules.scopes));
+    } else {
+        if subject.starts_with(|c: char| c.is_lowercase()) {
+            problems.push(Problem::warning(1, "Capitalize the subject"));
+        }
+        if subject.ends_with('.') {
+            problems.push(Problem::warning(1, "Do not end the subject with a period"));
+        }
+        problems.extend(lint_mood(subject));
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+fn lint_conventional(message: &str, lines: &[&str], scopes: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    match ConventionalCommit::parse(message) {
+        None => problems.push(Problem::error(
+            1,
+            "The subject must have the form '<type>[optional scope][!]: <description>'",
+        )),
+        Some(commit) => {
+            if !commit.has_known_type() {
+                problems.push(Problem::error(
+                    1,
+                    format!(
+                        "Unknown type '{}', use one of: {}",
+                        commit.commit_type,
+                        TYPES.join(", ")
+                    ),
+                ));
+            }
+            match commit.scope.as_deref() {
+                Some("") => problems.push(Problem::error(
+                    1,
+                    "The scope is empty, name it or remove the parentheses",
+                )),
+                Some(scope) if scope.contains(char::is_whitespace) => {
+                    problems.push(Problem::error(
+                        1,
+                        format!(
+                            "The scope '{}' must be a single noun, such as 'parser'",
+                            scope
+                        ),
+                    ))
+                }
+                Some(scope) if !scopes.is_empty() && !scopes.iter().any(|s| s == scope) => problems
+                    .push(Problem::error(
+                        1,
+                        format!(
+                            "The scope '{}' is not allowed, use one of: {}",
+                            scope,
+                            scopes.join(", ")
+                        ),
+                    )),
+                _ => {}
+            }
+
+            let description = commit.description.trim();
+            if description.is_empty() {
+                problems.push(Problem::error(1, "The description is empty"));
+            } else {
+                if is_capitalized(description) {
+                    problems.push(Problem::error(
+                        1,
+                        "Start the description with a lowercase letter",
+                    ));
+                }
+                if description.ends_with('.') {
+                    problems.push(Problem::error(
+                        1,
+                        "Do not end the description with a period",
+                    ));
+                }
+                problems.extend(lint_mood(description));
+            }
+        }
+    }
+
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        if BREAKING_FOOTER_LIKE.is_match(line) && !BREAKING_FOOTER.is_match(line) {
+            problems.push(Problem::error(
+                index + 1,
+                "Write breaking changes as a 'BREAKING CHANGE: <description>' footer",
+            ));
+        }
+    }
+
+    problems
+}
+
+/// A warning when the text starts with a past tense, third person or
+/// gerund form of a common verb, such as "Added", "Adds" or "Adding".
+fn lint_mood(text: &str) -> Option<Problem> {
+    let word = text.split_whitespace().next()?;
+    let lowercase = word.to_lowercase();
+    if VERBS.contains(&lowercase.as_str()) {
+        return None;
+    }
+
+    let verb = VERBS
+        .iter()
+        .find(|verb| inflections(verb).contains(&lowercase))?;
+    let imperative = if word.starts_with(char::is_uppercase) {
+        capitalize(verb)
+    } else {
+        verb.to_string()
+    };
+
+    Some(Problem::warning(
+        1,
+        format!(
+            "Use the imperative mood, '{}' rather than '{}'",
+            imperative, word
+        ),
+    ))
+}
+
+fn inflections(verb: &str) -> Vec<String> {
+    let mut forms = vec![
+        format!("{}s", verb),
+        format!("{}es", verb),
+        format!("{}ed", verb),
+        format!("{}d", verb),
+        format!("{}ing", verb),
+        format!("{}ing", verb.trim_end_matches('e')),
+    ];
+    if let Some(last) = verb.chars().last() {
+        forms.push(format!("{}{}ed", verb, last));
+        forms.push(format!("{}{}ing", verb, last));
+    }
+    if let Some(stem) = verb.strip_suffix('y') {
+        forms.push(format!("{}ies", stem));
+        forms.push(format!("{}ied", stem));
+    }
+    forms
+}
+
+fn is_capitalized(text: &str) -> bool {
+    let mut chars = text.chars();
+    // Acronyms such as "API" are fine
+    chars.next().is_some_and(char::is_uppercase) && chars.next().is_some_and(char::is_lowercase)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Whether the message was written by git or is meant to be squashed, and
+/// is not held to the rules.
+pub fn is_exempt(message: &str) -> bool {
+    EXEMPT_PREFIXES
+        .iter()
+        .any(|prefix| message.starts_with(prefix))
+}
+
+/// The message git will commit from a message file: lines starting with
+/// `comment`, git's `core.commentChar`, and everything below the scissors
+/// line of `git commit --verbose` removed.
+pub fn strip_comments(text: &str, comment: &str) -> String {
+    let scissors = format!("{}{}", comment, SCISSORS);
+    let lines: Vec<&str> = text
+        .lines()
+        .take_while(|line| *line != scissors)
+        .filter(|line| !line.starts_with(comment))
+        .collect();
+
+    lines.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conventional() -> Rules {
+        Rules {
+            conventional: true,
+            ..Rules::default()
+        }
+    }
+
+    fn messages(problems: Vec<Problem>) -> Vec<String> {
+        problems.iter().map(Problem::to_string).collect()
+    }
+
+    #[test]
+    fn test_lint_best_practices() {
+        assert_eq!(
+            lint(
+                "Add login form\n\nUsers can sign in with a password.",
+                &Rules::default()
+            ),
+            vec![]
+        );
+        assert_eq!(
+            messages(lint(
+                "Add a login form with a password field and a remember me box\nSo users can sign in.\n\nThe form is shown on every page that needs an account, except the landing page.\nhttps://example.com/a/very/long/link/that/cannot/be/wrapped/at/seventy/two/columns",
+                &Rules::default()
+            )),
+            vec![
+                "1: error: The subject is 60 characters long, keep it to 50 or less",
+                "2: error: Separate the subject from the body with a blank line",
+                "4: error: The line is 79 characters long, wrap the body at 72",
+            ]
+        );
+        assert_eq!(
+            messages(lint("fixed the login form.", &Rules::default())),
+            vec![
+                "1: warning: Capitalize the subject",
+                "1: warning: Do not end the subject with a period",
+                "1: warning: Use the imperative mood, 'fix' rather than 'fixed'",
+            ]
+        );
+        assert_eq!(
+            messages(lint("\n\n", &Rules::default())),
+            vec!["1: error: The message is empty"]
+        );
+        // Conventional commits are held to the conventional rules instead
+        assert_eq!(lint("fix: handle empty body", &Rules::default()), vec![]);
+        assert_eq!(
+            messages(lint("fix: Handle empty body", &Rules::default())),
+            vec!["1: error: Start the description with a lowercase letter"]
+        );
+    }
+
+    #[test]
+    fn test_lint_mood() {
+        for (subject, imperative) in [
+            ("Added login", "Add"),
+            ("Adds login", "Add"),
+            ("Fixes race", "Fix"),
+            ("Dropped Node 16", "Drop"),
+            ("Using rustls", "Use"),
+            ("Simplified parser", "Simplify"),
+            ("Writing docs", "Write"),
+        ] {
+            let problem = lint_mood(subject).unwrap();
+            assert!(
+                problem.message.contains(&format!("'{}'", imperative)),
+                "{}: {}",
+                subject,
+                problem.message
+            );
+        }
+        assert_eq!(lint_mood("Add login"), None);
+        assert_eq!(lint_mood("Rename settings"), None);
+        assert_eq!(lint_mood("Readme tweaks"), None);
+    }
+
+    #[test]
+    fn test_lint_conventional() {
+        assert_eq!(
+            lint("feat(auth): add OAuth2 integration", &conventional()),
+            vec![]
+        );
+        assert_eq!(
+            lint(
+                "fix(database): prevent SQL injection\n\nBREAKING CHANGE: sanitize() now returns a Promise",
+                &conventional()
+            ),
+            vec![]
+        );
+        assert_eq!(
+            lint("feat(api)!: change response format", &conventional()),
+            vec![]
+        );
+
+        assert_eq!(
+            messages(lint("Add login form", &conventional())),
+            vec![
+                "1: error: The subject must have the form '<type>[optional scope][!]: <description>'"
+            ]
+        );
+        assert_eq!(
+            messages(lint("feature(): Added login.", &conventional())),
+            vec![
+                "1: error: Unknown type 'feature', use one of: feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert",
+                "1: error: The scope is empty, name it or remove the parentheses",
+                "1: error: Start the description with a lowercase letter",
+                "1: error: Do not end the description with a period",
+                "1: warning: Use the imperative mood, 'Add' rather than 'Added'",
+            ]
+        );
+        let rules = Rules {
+            conventional: true,
+            scopes: vec!["api".to_string(), "web".to_string()],
+        };
+        assert_eq!(lint("fix(api): handle timeouts", &rules), vec![]);
+        assert_eq!(lint("fix: handle timeouts", &rules), vec![]);
+        assert_eq!(
+            messages(lint("fix(parser): handle timeouts", &rules)),
+            vec!["1: error: The scope 'parser' is not allowed, use one of: api, web"]
+        );
+        assert_eq!(
+            messages(lint("fix(user auth): handle API errors", &conventional())),
+            vec!["1: error: The scope 'user auth' must be a single noun, such as 'parser'"]
+        );
+        assert_eq!(
+            messages(lint(
+                "feat: drop Node 16\n\nBreaking change: Node 18 is required",
+                &conventional()
+            )),
+            vec!["3: error: Write breaking changes as a 'BREAKING CHANGE: <description>' footer"]
+        );
+    }
+
+    #[test]
+    fn test_exempt_and_strip_comments() {
+        assert!(is_exempt("fixup! Add login form"));
+        assert!(is_exempt("Merge branch 'main' into feature"));
+        assert!(is_exempt(
+            "Revert \"Add login form\"\n\nThis reverts commit abc."
+        ));
+        assert!(!is_exempt("Add login form"));
+
+        assert_eq!(
+            strip_comments(
+                "Add login form\n\nWith a body.\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n",
+                "#"
+            ),
+            "Add login form\n\nWith a body."
+        );
+        assert_eq!(
+            strip_comments(
+                "Add login form\n\n#123 is fixed.\n; Please enter the commit message\n; ------------------------ >8 ------------------------\n# diff\n",
+                ";"
+            ),
+            "Add login form\n\n#123 is fixed."
+        );
+    }
+}
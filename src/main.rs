@@ -1,14 +1,47 @@
-use clap::{Parser, Subcommand};
-use prompt::build_prompt;
+use changelog::Entry;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::Config;
+use conventional::ConventionalCommit;
+use credentials::CredentialStore;
+use prompt::{PromptOptions, build_classification_prompt, build_pr_prompt, build_prompt};
+use provider::ProviderKind;
+use serde::Serialize;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use tokio::runtime::Runtime;
 
 pub mod anthropic;
 mod api;
+mod bump;
+mod changelog;
+mod config;
+mod conventional;
+mod credentials;
+mod diff;
+mod error;
+mod git;
+mod hook;
+mod http;
+mod lint;
+mod menu;
+pub mod ollama;
+pub mod openai;
+mod pr;
 mod prompt;
+mod provider;
+mod redact;
+mod repair;
+mod retry;
+mod reword;
+mod sse;
+
+// How many times a message that is not a conventional commit is sent back
+// to the model by default
+const REPAIR_ATTEMPTS: u32 = 2;
+// What `git diff` compares against before the first commit
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
 
 #[derive(Parser)]
 #[command(name = "gitai")]
@@ -23,24 +56,265 @@ enum Commands {
     /// Initialize gitai with your API key
     Init,
     /// Generate a commit message based on staged changes
-    Commit {
-        /// Use conventional commit messages
-        #[arg(short = 'c', long = "conventional")]
-        conventional: bool,
+    Commit(CommitArgs),
+    /// Write new messages for the commits in a range and rewrite them
+    Reword(RewordArgs),
+    /// Write a pull request title and description for the current branch
+    #[command(visible_alias = "mr")]
+    Pr(PrArgs),
+    /// Write a changelog section for the commits in a range
+    Changelog(ChangelogArgs),
+    /// Suggest the next version from the commits since the last version tag
+    Bump(BumpArgs),
+    /// Check commit messages against the rules gitai writes them by
+    Lint(LintArgs),
+    /// Manage the hooks that generate messages for plain `git commit` or lint them
+    Hook {
+        #[command(subcommand)]
+        command: HookCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Install the hook in the current repository
+    Install {
+        /// Install the commit-msg hook that runs `gitai lint` instead
+        #[arg(long = "lint")]
+        lint: bool,
+    },
+    /// Remove the hook installed by gitai
+    Uninstall {
+        /// Remove the commit-msg hook that runs `gitai lint` instead
+        #[arg(long = "lint")]
+        lint: bool,
+    },
+    /// Fill in the commit message file; called by git
+    Run {
+        /// The file holding the commit message
+        message_file: PathBuf,
+        /// Where the message comes from: message, template, merge, squash or commit
+        source: Option<String>,
+        /// The commit being amended, for the commit source
+        commit: Option<String>,
     },
 }
 
+/// Options for every command that asks the model for a message.
+#[derive(Args, Default)]
+struct GenerationArgs {
+    /// Use conventional commit messages
+    #[arg(short = 'c', long = "conventional")]
+    conventional: bool,
+    #[command(flatten)]
+    provider: ProviderArgs,
+}
+
+#[derive(Args, Clone, Default)]
+struct ProviderArgs {
+    /// Model to use, overriding GITAI_MODEL and the config files
+    #[arg(long = "model")]
+    model: Option<String>,
+    /// Read the API key from this environment variable
+    #[arg(long = "api-key-env", value_name = "VAR")]
+    api_key_env: Option<String>,
+}
+
+#[derive(Args, Default)]
+struct CommitArgs {
+    #[command(flatten)]
+    generation: GenerationArgs,
+    /// Generate several messages and pick one from a menu
+    #[arg(long = "candidates", value_name = "N", value_parser = clap::value_parser!(u8).range(2..=9))]
+    candidates: Option<u8>,
+    /// After generating, ask for feedback and refine the message until accepted
+    #[arg(long = "refine", conflicts_with = "candidates")]
+    refine: bool,
+    /// Print the message instead of committing with it
+    #[arg(long = "dry-run", conflicts_with_all = ["candidates", "refine"])]
+    dry_run: bool,
+    /// How --dry-run prints the message
+    #[arg(long = "format", value_enum, default_value_t, requires = "dry_run")]
+    format: OutputFormat,
+    /// Commit all changes to tracked files, like `git commit --all`
+    #[arg(short = 'a', long = "all")]
+    all: bool,
+    /// Replace the last commit, with a message for it and any staged changes
+    #[arg(long = "amend")]
+    amend: bool,
+    /// Arguments passed on to `git commit`, such as `-- -S --no-verify`
+    #[arg(last = true, value_name = "GIT_ARGS")]
+    git_args: Vec<String>,
+}
+
+impl CommitArgs {
+    /// Arguments for `git commit` besides the message.
+    fn git_commit_args(&self) -> Vec<String> {
+        let mut git_args = Vec::new();
+        if self.all {
+            git_args.push("--all".to_string());
+        }
+        if self.amend {
+            git_args.push("--amend".to_string());
+        }
+        git_args.extend(self.git_args.iter().cloned());
+        git_args
+    }
+}
+
+#[derive(Args)]
+struct RewordArgs {
+    /// The commits to reword, such as `main..` or `HEAD~5..`
+    range: String,
+    #[command(flatten)]
+    generation: GenerationArgs,
+    /// Rewrite the commits without asking first
+    #[arg(short = 'y', long = "yes")]
+    yes: bool,
+}
+
+#[derive(Args)]
+struct PrArgs {
+    /// The branch to merge into, by default the remote's default branch, main or master
+    #[arg(long = "base")]
+    base: Option<String>,
+    /// Write the description to this file instead of stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    generation: GenerationArgs,
+}
+
+#[derive(Args)]
+struct ChangelogArgs {
+    /// The commits to include, such as `v1.2.0..v1.3.0` or `v1.2.0..`
+    range: String,
+    /// The version in the heading, `Unreleased` if not given
+    #[arg(long = "release", value_name = "VERSION")]
+    release: Option<String>,
+    /// Add the section to the top of CHANGELOG.md instead of printing it
+    #[arg(short = 'w', long = "write")]
+    write: bool,
+    #[command(flatten)]
+    provider: ProviderArgs,
+}
+
+#[derive(Args)]
+struct BumpArgs {
+    /// Create an annotated tag for the new version, with release notes
+    #[arg(long = "tag")]
+    tag: bool,
+    #[command(flatten)]
+    provider: ProviderArgs,
+}
+
+#[derive(Args)]
+struct LintArgs {
+    /// A message file, a commit or a range such as `main..`, by default the last commit
+    target: Option<String>,
+    /// Check the conventional commit rules too
+    #[arg(short = 'c', long = "conventional")]
+    conventional: bool,
+    /// Lint the message file git passes to the commit-msg hook, aborting the commit on errors
+    #[arg(long = "hook", requires = "target")]
+    hook: bool,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    /// Just the message
+    #[default]
+    Text,
+    /// The message with the model, token usage and the files left out
+    Json,
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
         Commands::Init => handle_init(),
-        Commands::Commit { conventional } => handle_commit(conventional),
+        Commands::Commit(args) => handle_commit(args),
+        Commands::Reword(args) => handle_reword(args),
+        Commands::Pr(args) => handle_pr(args),
+        Commands::Changelog(args) => handle_changelog(args),
+        Commands::Bump(args) => handle_bump(args),
+        Commands::Lint(args) => handle_lint(args),
+        Commands::Hook { command } => match command {
+            HookCommands::Install { lint } => handle_hook_install(hook_kind(lint)),
+            HookCommands::Uninstall { lint } => handle_hook_uninstall(hook_kind(lint)),
+            HookCommands::Run {
+                message_file,
+                source,
+                ..
+            } => handle_hook_run(&message_file, source.as_deref()),
+        },
     }
 }
 
 fn handle_init() {
-    print!("Enter your Anthropic API key: ");
+    let kind = loop {
+        let input = read_line("Select provider (anthropic, openai, ollama) [anthropic]: ");
+        if input.is_empty() {
+            break ProviderKind::Anthropic;
+        }
+        match input.parse::<ProviderKind>() {
+            Ok(kind) => break kind,
+            Err(e) => eprintln!("{}", e),
+        }
+    };
+
+    let config_dir = get_config_dir();
+    let mut config = match Config::load(&config_dir) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    config.provider = kind;
+    config.base_url = None;
+    config.model = None;
+
+    let default_base_url = match kind {
+        ProviderKind::Anthropic => None,
+        ProviderKind::OpenAi => Some("https://api.openai.com/v1"),
+        ProviderKind::Ollama => Some("http://localhost:11434"),
+    };
+
+    if let Some(default_base_url) = default_base_url {
+        let base_url = read_line(&format!("Enter the API base URL [{}]: ", default_base_url));
+        config.base_url = Some(base_url).filter(|url| !url.is_empty());
+
+        let model = read_line(&format!("Enter the model [{}]: ", kind.default_model()));
+        config.model = Some(model).filter(|model| !model.is_empty());
+    }
+
+    match config.save(&config_dir) {
+        Ok(path) => println!("Configuration saved to {:?}", path),
+        Err(e) => {
+            eprintln!("Failed to save configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if kind.requires_api_key() {
+        let api_key = match kind {
+            ProviderKind::Anthropic => read_line("Enter your Anthropic API key: "),
+            _ => read_line("Enter your API key: "),
+        };
+
+        let store = credentials::default_store(&config_dir);
+        match store_api_key(&api_key, &config_dir, store.as_ref()) {
+            Ok(()) => println!("API key saved to {}", store.location()),
+            Err(e) => eprintln!("Failed to save API key: {}", e),
+        }
+    }
+}
+
+fn read_line(label: &str) -> String {
+    print!("{}", label);
     io::stdout().flush().unwrap();
 
     let mut input = String::new();
@@ -48,16 +322,10 @@ fn handle_init() {
         .read_line(&mut input)
         .expect("Failed to read input");
 
-    let api_key = input.trim();
-    let config_dir = get_config_dir();
-
-    match store_api_key(api_key, &config_dir) {
-        Ok(path) => println!("API key saved to {:?}", path),
-        Err(e) => eprintln!("Failed to save API key: {}", e),
-    }
+    input.trim().to_string()
 }
 
-fn handle_commit(use_conventional: bool) {
+fn handle_commit(args: CommitArgs) {
     match is_git_repository() {
         Ok(true) => {}
         Ok(false) => {
@@ -70,37 +338,831 @@ fn handle_commit(use_conventional: bool) {
         }
     }
 
-    let diff = get_staged_changes().expect("Failed to run git diff --cached");
+    let diff =
+        get_changes_at(Path::new("."), args.all, args.amend).expect("Failed to run git diff");
+    let git_args = args.git_commit_args();
 
     if diff.is_empty() {
-        run_git_commit(None);
+        if args.dry_run {
+            eprintln!("Error: No staged changes");
+            std::process::exit(1);
+        }
+        run_git_commit(None, true, &git_args);
         return;
     }
 
-    let config_dir = get_config_dir();
-    let api_key = match read_api_key(&config_dir) {
-        Ok(key) => key,
+    let generation = (|| -> Result<Generation, Box<dyn std::error::Error>> {
+        let mut generation = Generation::prepare(&args.generation, &diff)?;
+        warn_secrets_staged(&generation);
+        if args.amend {
+            generation.prompt_options.previous_message = Some(get_head_message_at(Path::new("."))?);
+        }
+        Ok(generation)
+    })();
+    let generation = match generation {
+        Ok(generation) => generation,
         Err(e) => {
             eprintln!("Error: {}", e);
             std::process::exit(1);
         }
     };
 
-    let prompt = build_prompt(&diff, use_conventional);
-    println!("Generating commit message...");
-
     let rt = Runtime::new().expect("Failed to create Tokio runtime");
+    if let Some(count) = args.candidates {
+        choose_candidate(&rt, &generation, count.into(), &git_args);
+    }
+    if args.refine {
+        refine_message(&rt, &generation, &git_args);
+    }
+    if args.dry_run {
+        print_message(&rt, &generation, args.format);
+        return;
+    }
+
     let commit_message = rt.block_on(async {
-        match api::generate_commit_message(&api_key, &prompt).await {
+        let result = async {
+            let prompt = generation.prompt().await?;
+
+            let message = if io::stdout().is_terminal() {
+                println!("Generating commit message...\n");
+                let mut print_text = |text: &str| {
+                    print!("{}", text);
+                    io::stdout().flush().unwrap();
+                };
+                let result = api::stream_commit_message(
+                    generation.provider.as_ref(),
+                    &prompt,
+                    &mut print_text,
+                )
+                .await;
+                println!("\n");
+                result
+            } else {
+                println!("Generating commit message...");
+                api::generate(generation.provider.as_ref(), &prompt).await
+            }?;
+            generation.repair(&prompt, &message).await
+        }
+        .await;
+
+        match result {
             Ok(msg) => msg,
-            Err(e) => {
-                eprintln!("Error generating commit message: {}", e);
+            Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+        }
+    });
+
+    run_git_commit(Some(&commit_message), true, &git_args);
+}
+
+fn choose_candidate(rt: &Runtime, generation: &Generation, count: usize, git_args: &[String]) -> ! {
+    if !io::stdin().is_terminal() {
+        eprintln!("Error: --candidates needs an interactive terminal");
+        std::process::exit(1);
+    }
+
+    let prompt = match rt.block_on(generation.prompt()) {
+        Ok(prompt) => prompt,
+        Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+    };
+
+    loop {
+        println!("Generating {} commit messages...\n", count);
+        let result = rt.block_on(async {
+            let candidates =
+                api::generate_candidates(generation.provider.as_ref(), &prompt, count).await?;
+            futures::future::try_join_all(
+                candidates
+                    .iter()
+                    .map(|candidate| generation.repair(&prompt, candidate)),
+            )
+            .await
+        });
+        let candidates = match result {
+            Ok(candidates) => candidates,
+            Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+        };
+
+        let choice = menu::choose(&candidates, &mut io::stdin().lock(), &mut io::stdout())
+            .expect("Failed to read input");
+        match choice {
+            menu::Choice::Use(index) => run_git_commit(Some(&candidates[index]), false, git_args),
+            menu::Choice::Edit(index) => run_git_commit(Some(&candidates[index]), true, git_args),
+            menu::Choice::Regenerate => println!(),
+            menu::Choice::Abort => {
+                eprintln!("Aborted, nothing was committed");
                 std::process::exit(1);
             }
         }
+    }
+}
+
+fn refine_message(rt: &Runtime, generation: &Generation, git_args: &[String]) -> ! {
+    if !io::stdin().is_terminal() {
+        eprintln!("Error: --refine needs an interactive terminal");
+        std::process::exit(1);
+    }
+
+    let prompt = match rt.block_on(generation.prompt()) {
+        Ok(prompt) => prompt,
+        Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+    };
+
+    let mut conversation = vec![provider::Message::user(prompt)];
+    println!("Generating commit message...\n");
+    loop {
+        let mut print_text = |text: &str| {
+            print!("{}", text);
+            io::stdout().flush().unwrap();
+        };
+        let result = rt.block_on(async {
+            let streamed = api::refine_commit_message(
+                generation.provider.as_ref(),
+                &conversation,
+                &mut print_text,
+            )
+            .await;
+            println!("\n");
+            let streamed = streamed?;
+            // What is committed on Enter has to be what was shown
+            let message = generation
+                .repair(&conversation[0].content, &streamed)
+                .await?;
+            if message != streamed {
+                println!("Cleaned up to:\n\n{}\n", message);
+            }
+            Ok(message)
+        });
+        let message = match result {
+            Ok(message) => message,
+            Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+        };
+
+        let feedback = menu::ask_feedback(&mut io::stdin().lock(), &mut io::stdout())
+            .expect("Failed to read input");
+        match feedback {
+            menu::Feedback::Accept => run_git_commit(Some(&message), false, git_args),
+            menu::Feedback::Refine(feedback) => {
+                conversation.push(provider::Message::assistant(message));
+                conversation.push(provider::Message::user(feedback));
+                println!("\nRefining commit message...\n");
+            }
+            menu::Feedback::Abort => {
+                eprintln!("Aborted, nothing was committed");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// What `--dry-run --format json` prints.
+#[derive(Serialize)]
+struct DryRun<'a> {
+    message: &'a str,
+    model: &'a str,
+    /// Summed over all requests, absent if the provider does not report it
+    usage: Option<provider::Usage>,
+    elided: &'a [diff::Elided],
+}
+
+// Everything but the result goes to stderr, so scripts can read stdout as is
+fn print_message(rt: &Runtime, generation: &Generation, format: OutputFormat) {
+    let result = rt.block_on(async {
+        let prompt = generation.prompt().await?;
+        let message = api::generate(generation.provider.as_ref(), &prompt).await?;
+        generation.repair(&prompt, &message).await
     });
+    let message = match result {
+        Ok(message) => message,
+        Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+    };
+
+    match format {
+        OutputFormat::Text => println!("{}", message),
+        OutputFormat::Json => {
+            let dry_run = DryRun {
+                message: &message,
+                model: &generation.model,
+                usage: generation.provider.usage(),
+                elided: &generation.elided,
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&dry_run).expect("Failed to serialize output")
+            );
+        }
+    }
+}
+
+fn handle_reword(args: RewordArgs) {
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let repository = Path::new(".");
+        if !is_git_repository()? {
+            return Err("Not a git repository".into());
+        }
+        if reword::has_changes_at(repository)? {
+            return Err("Commit or stash your changes before rewording commits".into());
+        }
+        if !args.yes && !io::stdin().is_terminal() {
+            return Err("Confirming needs an interactive terminal, pass --yes to skip it".into());
+        }
+
+        let commits = reword::commits_at(repository, &args.range)?;
+        let mut generation = Generation::load(&args.generation)?;
+        let rt = Runtime::new()?;
+
+        let mut rewordings = Vec::new();
+        for (index, commit) in commits.iter().enumerate() {
+            let old_message = reword::message_at(repository, commit)?;
+            println!(
+                "Generating commit message {}/{} for {:.7} {}",
+                index + 1,
+                commits.len(),
+                commit,
+                old_message.lines().next().unwrap_or_default()
+            );
+
+            let diff = reword::diff_at(repository, commit)?;
+            let new_message = if diff.is_empty() {
+                old_message.clone()
+            } else {
+                generation = generation.with_diff(&diff)?;
+                let result = rt.block_on(async {
+                    let prompt = generation.prompt().await?;
+                    let message = api::generate(generation.provider.as_ref(), &prompt).await?;
+                    generation.repair(&prompt, &message).await
+                });
+                match result {
+                    Ok(message) => message,
+                    Err(e) => exit_with_generation_error(GENERATING_COMMIT_MESSAGE, &e),
+                }
+            };
+
+            rewordings.push(reword::Rewording {
+                commit: commit.clone(),
+                old_message,
+                new_message,
+            });
+        }
+
+        println!();
+        for line in reword::table(&rewordings) {
+            println!("{}", line);
+        }
+        println!();
+
+        if !args.yes {
+            let answer = read_line(&format!("Rewrite {} commits? [y/N] ", rewordings.len()));
+            if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+                println!("Nothing was changed");
+                return Ok(());
+            }
+        }
+
+        reword::rewrite_at(repository, &rewordings)?;
+        println!("Reworded {} commits", rewordings.len());
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    run_git_commit(Some(&commit_message));
+// Progress goes to stderr, so the description can be redirected as is
+fn handle_pr(args: PrArgs) {
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let repository = Path::new(".");
+        if !is_git_repository()? {
+            return Err("Not a git repository".into());
+        }
+
+        let base = match args.base {
+            Some(base) => base,
+            None => pr::default_base_at(repository)?,
+        };
+        let branch = pr::branch_at(repository, &base)?;
+        let generation = Generation::prepare(&args.generation, &branch.diff)?;
+
+        eprintln!(
+            "Generating a description of {} commits since {}...",
+            branch.commits.len(),
+            base
+        );
+        let rt = Runtime::new()?;
+        let result = rt.block_on(async {
+            let changes = generation.changes().await?;
+            let prompt = build_pr_prompt(&changes, &branch.commits, &generation.prompt_options);
+            api::generate(generation.provider.as_ref(), &prompt).await
+        });
+        let description = match result {
+            Ok(description) => description,
+            Err(e) => exit_with_generation_error("generating the pull request description", &e),
+        };
+
+        match &args.output {
+            Some(path) => {
+                fs::write(path, format!("{}\n", description))?;
+                eprintln!("Description saved to {:?}", path);
+            }
+            None => println!("{}", description),
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn handle_changelog(args: ChangelogArgs) {
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let repository = Path::new(".");
+        if !is_git_repository()? {
+            return Err("Not a git repository".into());
+        }
+
+        let messages = git::messages_at(repository, &args.range)?;
+        if messages.is_empty() {
+            return Err(format!("No commits in '{}'", args.range).into());
+        }
+
+        let rt = Runtime::new()?;
+        let commits = classify_commits(&rt, &messages, &args.provider)?;
+        let entries = changelog_entries(&messages, &commits);
+        let heading = match &args.release {
+            Some(release) => {
+                // The date of the last commit in the range, the release date
+                // when it is tagged
+                let end = args
+                    .range
+                    .split_once("..")
+                    .map(|(_, end)| end.trim_start_matches('.'))
+                    .filter(|end| !end.is_empty())
+                    .unwrap_or("HEAD");
+                let date = git::git_at(repository, &["log", "-1", "--format=%cs", end])?;
+                format!("[{}] - {}", release.trim_start_matches('v'), date.trim())
+            }
+            None => "[Unreleased]".to_string(),
+        };
+        let section = changelog::render(&heading, &entries);
+
+        if args.write {
+            let changelog_file =
+                get_repository_root_at(repository)?.join(changelog::CHANGELOG_FILE);
+            changelog::prepend(&changelog_file, &section)?;
+            println!("Changelog updated at {:?}", changelog_file);
+        } else {
+            print!("{}", section);
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn handle_bump(args: BumpArgs) {
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let repository = Path::new(".");
+        if !is_git_repository()? {
+            return Err("Not a git repository".into());
+        }
+
+        let latest_tag = bump::latest_tag_at(repository)?;
+        let (range, prefix, current) = match &latest_tag {
+            Some((tag, version)) => {
+                let prefix = if tag.starts_with('v') { "v" } else { "" };
+                (format!("{}..HEAD", tag), prefix, *version)
+            }
+            None => ("HEAD".to_string(), "v", bump::Version::default()),
+        };
+        let messages = git::messages_at(repository, &range)?;
+        if messages.is_empty() {
+            return Err(match &latest_tag {
+                Some((tag, _)) => format!("No commits since {}", tag).into(),
+                None => "The repository has no commits yet".into(),
+            });
+        }
+
+        let rt = Runtime::new()?;
+        let commits = classify_commits(&rt, &messages, &args.provider)?;
+        let recommendation = bump::Recommendation::for_commits(&messages, &commits);
+        let bump = recommendation.bump.for_version(current);
+        let next = current.bump(bump);
+
+        match &latest_tag {
+            Some((tag, _)) => {
+                println!("Latest version: {} ({} commits since)", tag, messages.len())
+            }
+            None => println!("No version tag yet, starting from {}", current),
+        }
+        println!("Recommended: {} bump to {}{}", bump, prefix, next);
+        println!("\n{}:", recommendation.justification());
+        for reason in &recommendation.reasons {
+            println!("  - {}", reason);
+        }
+
+        if args.tag {
+            let tag = format!("{}{}", prefix, next);
+            let notes = changelog::render_notes(&changelog_entries(&messages, &commits));
+            bump::create_tag_at(repository, &tag, &format!("Release {}\n{}", next, notes))?;
+            println!("\nCreated tag {}", tag);
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Parses the messages as conventional commits, asking the model for the
+/// type of the ones that are not. `None` for those it could not classify.
+fn classify_commits(
+    rt: &Runtime,
+    messages: &[String],
+    provider_args: &ProviderArgs,
+) -> Result<Vec<Option<ConventionalCommit>>, Box<dyn std::error::Error>> {
+    let parsed: Vec<Option<ConventionalCommit>> = messages
+        .iter()
+        .map(|message| ConventionalCommit::parse(message).filter(|commit| commit.has_known_type()))
+        .collect();
+    let unparsed: Vec<String> = messages
+        .iter()
+        .zip(&parsed)
+        .filter(|(_, commit)| commit.is_none())
+        .map(|(message, _)| message.clone())
+        .collect();
+
+    let mut classified = if unparsed.is_empty() {
+        Vec::new()
+    } else {
+        let generation = Generation::load(&GenerationArgs {
+            conventional: false,
+            provider: provider_args.clone(),
+        })?;
+        eprintln!("Classifying {} commits...", unparsed.len());
+        let prompt = build_classification_prompt(&unparsed);
+        let result = rt.block_on(api::generate(generation.provider.as_ref(), &prompt));
+        match result {
+            Ok(response) => conventional::parse_classification(&response, unparsed.len()),
+            Err(e) => exit_with_generation_error("classifying the commits", &e),
+        }
+    }
+    .into_iter();
+
+    Ok(messages
+        .iter()
+        .zip(parsed)
+        .map(|(message, commit)| {
+            commit.or_else(|| {
+                let commit_type = classified.next().flatten()?;
+                Some(ConventionalCommit {
+                    commit_type,
+                    scope: None,
+                    breaking: conventional::has_breaking_footer(message),
+                    description: subject(message).to_string(),
+                })
+            })
+        })
+        .collect())
+}
+
+/// Changelog entries for the classified commits.
+fn changelog_entries(messages: &[String], commits: &[Option<ConventionalCommit>]) -> Vec<Entry> {
+    messages
+        .iter()
+        .zip(commits)
+        .filter_map(|(message, commit)| match commit {
+            Some(commit) => Entry::from_conventional(commit),
+            None => Some(Entry::unclassified(subject(message))),
+        })
+        .collect()
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+fn hook_kind(lint: bool) -> hook::Hook {
+    if lint {
+        hook::Hook::CommitMsg
+    } else {
+        hook::Hook::PrepareCommitMsg
+    }
+}
+
+// Problems go to stderr in hook mode, where git shows them above its own
+// message about the aborted commit
+fn handle_lint(args: LintArgs) {
+    let result = (|| -> Result<bool, Box<dyn std::error::Error>> {
+        let repository = Path::new(".");
+        // A message file can be linted anywhere, the repository's settings
+        // only apply inside one
+        let mut config = Config::load(&get_config_dir())?;
+        if is_git_repository()? {
+            config = config.with_repository_config(&get_repository_root_at(repository)?)?;
+        }
+        let config = config.with_env(|name| std::env::var(name).ok());
+        let rules = lint::Rules {
+            conventional: args.conventional || config.conventional,
+            scopes: config.scopes,
+        };
+
+        // Each message with where it comes from: the file or the commit
+        let messages: Vec<(String, String)> = match args.target.as_deref() {
+            Some(target) if args.hook || Path::new(target).is_file() => {
+                vec![(
+                    target.to_string(),
+                    lint::strip_comments(
+                        &fs::read_to_string(target)?,
+                        &git::comment_string_at(repository),
+                    ),
+                )]
+            }
+            Some(range) if range.contains("..") => {
+                let commits = git::rev_list_at(repository, &["--reverse", "--no-merges", range])?;
+                commits
+                    .into_iter()
+                    .map(|commit| {
+                        let message = reword::message_at(repository, &commit)?;
+                        Ok((commit[..7].to_string(), message))
+                    })
+                    .collect::<Result<_, Box<dyn std::error::Error>>>()?
+            }
+            target => {
+                let commit = target.unwrap_or("HEAD");
+                vec![(commit.to_string(), reword::message_at(repository, commit)?)]
+            }
+        };
+
+        let mut errors = 0;
+        let mut warnings = 0;
+        for (location, message) in &messages {
+            if lint::is_exempt(message) || (args.hook && message.is_empty()) {
+                continue;
+            }
+            for problem in lint::lint(message, &rules) {
+                match problem.severity {
+                    lint::Severity::Error => errors += 1,
+                    lint::Severity::Warning => warnings += 1,
+                }
+                if args.hook {
+                    eprintln!("{}:{}", location, problem);
+                } else {
+                    println!("{}:{}", location, problem);
+                }
+            }
+        }
+
+        if args.hook {
+            if errors > 0 {
+                eprintln!(
+                    "gitai: commit message has {} errors, fix it or commit with --no-verify",
+                    errors
+                );
+            }
+        } else if errors > 0 || warnings > 0 {
+            println!(
+                "{} errors and {} warnings in {} messages",
+                errors,
+                warnings,
+                messages.len()
+            );
+        }
+        Ok(errors == 0)
+    })();
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn handle_hook_install(kind: hook::Hook) {
+    let gitai = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("gitai"));
+
+    match hook::hooks_dir_at(Path::new(".")).and_then(|dir| hook::install(&dir, kind, &gitai)) {
+        Ok(path) => println!("Hook installed at {:?}", path),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn handle_hook_uninstall(kind: hook::Hook) {
+    match hook::hooks_dir_at(Path::new(".")).and_then(|dir| hook::uninstall(&dir, kind)) {
+        Ok(path) => println!("Hook removed from {:?}", path),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// A failing hook would abort the commit, so problems are only reported and
+// the user is left to write the message themselves
+fn handle_hook_run(message_file: &Path, source: Option<&str>) {
+    if !hook::should_generate(source) {
+        return;
+    }
+
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let diff = get_changes_at(Path::new("."), false, false)?;
+        if diff.is_empty() {
+            return Ok(());
+        }
+
+        let generation = Generation::prepare(&GenerationArgs::default(), &diff)?;
+        warn_secrets_staged(&generation);
+        eprintln!("gitai: generating commit message...");
+        let rt = Runtime::new()?;
+        let message = rt.block_on(async {
+            let prompt = generation.prompt().await?;
+            let message = api::generate(generation.provider.as_ref(), &prompt).await?;
+            generation.repair(&prompt, &message).await
+        })?;
+
+        hook::write_message(message_file, &message)
+    })();
+
+    if let Err(e) = result {
+        eprintln!("gitai: could not generate a commit message: {}", e);
+    }
+}
+
+// Only for staged changes, a commit being reworded or described has its
+// secrets in the history already
+fn warn_secrets_staged(generation: &Generation) {
+    if generation.has_secrets {
+        eprintln!(
+            "The possible secrets are still staged. Run 'git restore --staged <file>' if they should not be committed."
+        );
+    }
+}
+
+const GENERATING_COMMIT_MESSAGE: &str = "generating the commit message";
+
+/// Reports a failed request, `action` being what it was for, e.g.
+/// "generating the commit message".
+fn exit_with_generation_error(action: &str, e: &error::Error) -> ! {
+    eprintln!("Error {}: {}", action, e);
+    if let Some(advice) = e.advice() {
+        eprintln!("{}", advice);
+    }
+    std::process::exit(1);
+}
+
+/// The staged changes made ready for the model: excluded files and secrets
+/// removed and the rest fitted to the token budget.
+struct Generation {
+    provider: Box<dyn provider::Provider>,
+    model: String,
+    config: Config,
+    prompt_options: PromptOptions,
+    diff: String,
+    /// Whether anything was redacted from the diff
+    has_secrets: bool,
+    chunks: Vec<String>,
+    elided: Vec<diff::Elided>,
+}
+
+impl Generation {
+    /// Loads the configuration and credentials and prepares the diff,
+    /// warning on stderr about anything redacted or left out.
+    fn prepare(args: &GenerationArgs, diff: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load(args)?.with_diff(diff)
+    }
+
+    /// Loads the configuration and credentials, leaving the diff empty.
+    fn load(args: &GenerationArgs) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_dir = get_config_dir();
+        let mut config = load_config(&config_dir)?.with_env(|name| std::env::var(name).ok());
+        if args.provider.model.is_some() {
+            config.model = args.provider.model.clone();
+        }
+        config.conventional |= args.conventional;
+
+        let settings = config.provider_settings();
+        let api_key = if settings.kind.requires_api_key() {
+            match api_key_from_env(
+                settings.kind,
+                args.provider.api_key_env.as_deref(),
+                |name| std::env::var(name).ok(),
+            )? {
+                Some(key) => key,
+                None => read_api_key(
+                    &config_dir,
+                    credentials::default_store(&config_dir).as_ref(),
+                )?,
+            }
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            provider: provider::build_provider(&settings, &api_key),
+            model: settings.model().to_string(),
+            config,
+            prompt_options: PromptOptions::default(),
+            diff: String::new(),
+            has_secrets: false,
+            chunks: Vec::new(),
+            elided: Vec::new(),
+        })
+    }
+
+    /// Replaces the diff, warning on stderr about anything redacted or left
+    /// out.
+    fn with_diff(self, diff: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = &self.config;
+        let max_diff_tokens = config.max_diff_tokens.unwrap_or(diff::MAX_DIFF_TOKENS);
+        let repository_root = get_repository_root_at(Path::new("."))?;
+        let excluded = diff::Exclusions::load(&repository_root, &config.exclude)?.apply(diff);
+
+        let redacted = redact::redact(&excluded.diff);
+        if !redacted.findings.is_empty() {
+            eprintln!(
+                "Warning: possible secrets were replaced with placeholders before sending the diff:"
+            );
+            for line in redact::summarize(&redacted.findings) {
+                eprintln!("  {}", line);
+            }
+        }
+
+        let budgeted = diff::fit_to_budget(&redacted.diff, max_diff_tokens, diff::MAX_CHUNKS);
+        if !budgeted.elided.is_empty() {
+            eprintln!("The diff is too large, leaving out of the prompt:");
+            for elided in &budgeted.elided {
+                eprintln!("  {}", elided);
+            }
+        }
+
+        let chunks = diff::chunk(&budgeted.diff, max_diff_tokens);
+        let elided = [excluded.elided, budgeted.elided].concat();
+        let prompt_options = PromptOptions {
+            conventional: config.conventional,
+            language: config.language.clone(),
+            scopes: config.scopes.clone(),
+            omitted: diff::omitted_notes(&elided),
+            summarized: chunks.len() > 1,
+            previous_message: None,
+        };
+
+        Ok(Self {
+            prompt_options,
+            has_secrets: !redacted.findings.is_empty(),
+            diff: budgeted.diff,
+            chunks,
+            elided,
+            ..self
+        })
+    }
+
+    /// Builds the commit message prompt.
+    async fn prompt(&self) -> Result<String, error::Error> {
+        Ok(build_prompt(&self.changes().await?, &self.prompt_options))
+    }
+
+    /// Cleans up a message the model wrote for `prompt`, asking again while
+    /// it is not a conventional commit in conventional mode.
+    async fn repair(&self, prompt: &str, message: &str) -> Result<String, error::Error> {
+        let rules = lint::Rules {
+            conventional: self.config.conventional,
+            scopes: self.config.scopes.clone(),
+        };
+        let attempts = self.config.repair_attempts.unwrap_or(REPAIR_ATTEMPTS);
+        api::repair_commit_message(self.provider.as_ref(), prompt, message, &rules, attempts).await
+    }
+
+    /// The diff, or summaries of its parts if it is too large for a single
+    /// prompt.
+    async fn changes(&self) -> Result<String, error::Error> {
+        if !self.prompt_options.summarized {
+            return Ok(self.diff.clone());
+        }
+
+        eprintln!("Summarizing the diff in {} parts...", self.chunks.len());
+        api::summarize_diff(self.provider.as_ref(), &self.chunks).await
+    }
+}
+
+/// The global config with the current repository's `.gitai.toml` on top.
+fn load_config(config_dir: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let config = Config::load(config_dir)?;
+    let repository_root = get_repository_root_at(Path::new("."))?;
+
+    config.with_repository_config(&repository_root)
 }
 
 fn get_config_dir() -> PathBuf {
@@ -109,8 +1171,33 @@ fn get_config_dir() -> PathBuf {
         .join(".gitai")
 }
 
-fn get_staged_changes() -> Result<String, Box<dyn std::error::Error>> {
-    let output = Command::new("git").arg("diff").arg("--cached").output()?;
+/// The staged changes, or with `all` every change to tracked files, which is
+/// what `git commit --all` would commit. With `amend` the changes of the last
+/// commit are included, so the diff is against its parent.
+fn get_changes_at(
+    path: &Path,
+    all: bool,
+    amend: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let base = match (amend, all) {
+        (true, _) => Some("HEAD^"),
+        (false, true) => Some("HEAD"),
+        (false, false) => None,
+    };
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(path).arg("diff");
+    if !all {
+        cmd.arg("--cached");
+    }
+    if let Some(base) = base {
+        if has_revision_at(path, base)? {
+            cmd.arg(base);
+        } else {
+            cmd.arg(EMPTY_TREE);
+        }
+    }
+    let output = cmd.output()?;
 
     if !output.status.success() {
         return Err("Failed to get git diff".into());
@@ -121,12 +1208,25 @@ fn get_staged_changes() -> Result<String, Box<dyn std::error::Error>> {
     Ok(diff)
 }
 
-fn run_git_commit(message: Option<&String>) {
+fn has_revision_at(path: &Path, revision: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let status = Command::new("git")
+        .current_dir(path)
+        .args(["rev-parse", "--verify", "--quiet", revision])
+        .stdout(std::process::Stdio::null())
+        .status()?;
+
+    Ok(status.success())
+}
+
+fn run_git_commit(message: Option<&String>, edit: bool, git_args: &[String]) {
     let mut cmd = Command::new("git");
-    cmd.arg("commit");
+    cmd.arg("commit").args(git_args);
 
     if let Some(message) = message {
-        cmd.arg("-m").arg(message).arg("--edit");
+        cmd.arg("-m").arg(message);
+        if edit {
+            cmd.arg("--edit");
+        }
     }
 
     match cmd.status() {
@@ -138,13 +1238,21 @@ fn run_git_commit(message: Option<&String>) {
     }
 }
 
-fn store_api_key(api_key: &str, config_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    fs::create_dir_all(config_dir)?;
+/// Saves the key in the credential store, dropping any plaintext copy left in
+/// the config file.
+fn store_api_key(
+    api_key: &str,
+    config_dir: &Path,
+    store: &dyn CredentialStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    store.set(api_key)?;
 
-    let config_file = config_dir.join("config");
-    fs::write(&config_file, api_key)?;
+    let mut config = Config::load(config_dir)?;
+    if config.api_key.take().is_some() {
+        config.save(config_dir)?;
+    }
 
-    Ok(config_file)
+    Ok(())
 }
 
 fn is_git_repository_at(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
@@ -157,19 +1265,81 @@ fn is_git_repository_at(path: &Path) -> Result<bool, Box<dyn std::error::Error>>
     Ok(output.status.success())
 }
 
+fn get_repository_root_at(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .current_dir(path)
+        .arg("rev-parse")
+        .arg("--show-toplevel")
+        .output()?;
+
+    if !output.status.success() {
+        return Err("Failed to find the repository root".into());
+    }
+
+    let root = String::from_utf8(output.stdout)?;
+    Ok(PathBuf::from(root.trim()))
+}
+
+fn get_head_message_at(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .current_dir(path)
+        .args(["log", "-1", "--format=%B"])
+        .output()?;
+
+    if !output.status.success() {
+        return Err("Nothing to amend, the repository has no commits yet".into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
 fn is_git_repository() -> Result<bool, Box<dyn std::error::Error>> {
     is_git_repository_at(Path::new("."))
 }
 
-fn read_api_key(config_dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+/// The API key from `--api-key-env`, which must be set, or else from the
+/// provider's usual variable such as `ANTHROPIC_API_KEY`. `None` means the
+/// key has to come from the credential store.
+fn api_key_from_env(
+    kind: ProviderKind,
+    api_key_env: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let value = |name: &str| {
+        env(name)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    match api_key_env {
+        Some(name) => match value(name) {
+            Some(api_key) => Ok(Some(api_key)),
+            None => Err(format!("Environment variable {} is not set or empty.", name).into()),
+        },
+        None => Ok(kind.api_key_env().and_then(value)),
+    }
+}
+
+fn read_api_key(
+    config_dir: &Path,
+    store: &dyn CredentialStore,
+) -> Result<String, Box<dyn std::error::Error>> {
     let config_file = config_dir.join("config");
     let file_exists = fs::exists(&config_file)?;
     if !file_exists {
         return Err("Config file not found. Please run 'gitai init' first.".into());
     }
 
-    let file_content = fs::read_to_string(config_file)?;
-    let api_key = file_content.trim().to_string();
+    // Keys written by older versions are still in the config file
+    let config = Config::load(config_dir)?;
+    let api_key = match config.api_key {
+        Some(api_key) if !api_key.trim().is_empty() => {
+            let api_key = api_key.trim().to_string();
+            store_api_key(&api_key, config_dir, store)?;
+            api_key
+        }
+        _ => store.get()?.unwrap_or_default().trim().to_string(),
+    };
 
     if api_key.is_empty() {
         return Err("API key is empty. Please run 'gitai init' first.".into());
@@ -181,6 +1351,7 @@ fn read_api_key(config_dir: &Path) -> Result<String, Box<dyn std::error::Error>>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use credentials::EncryptedFileStore;
     use std::fs;
     use std::path::Path;
     use tempfile::TempDir;
@@ -191,12 +1362,10 @@ mod tests {
         let config_dir = temp_dir.path().join(".gitai");
 
         let api_key = "test-api-key-123";
-        let result_path = store_api_key(api_key, &config_dir)?;
+        let store = EncryptedFileStore::new(&config_dir);
+        store_api_key(api_key, &config_dir, &store)?;
 
-        assert!(result_path.exists());
-
-        let content = fs::read_to_string(&result_path)?;
-        assert_eq!(content, api_key);
+        assert_eq!(store.get()?.as_deref(), Some(api_key));
 
         Ok(())
     }
@@ -217,6 +1386,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_repository_root_in_actual_git_repo() -> Result<(), Box<dyn std::error::Error>> {
+        // Use the src directory of this project, which is inside a git repo
+        let root = get_repository_root_at(Path::new("src"))?;
+        assert!(root.join("src").join("main.rs").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_repository_root_not_in_git_repo() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        assert!(get_repository_root_at(temp_dir.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_key_from_env() -> Result<(), Box<dyn std::error::Error>> {
+        let env = |name: &str| match name {
+            "ANTHROPIC_API_KEY" => Some("sk-ant-123\n".to_string()),
+            "CI_GITAI_KEY" => Some("sk-ci-456".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+
+        assert_eq!(
+            api_key_from_env(ProviderKind::Anthropic, None, env)?.as_deref(),
+            Some("sk-ant-123")
+        );
+        assert_eq!(api_key_from_env(ProviderKind::OpenAi, None, env)?, None);
+        assert_eq!(
+            api_key_from_env(ProviderKind::Anthropic, Some("CI_GITAI_KEY"), env)?.as_deref(),
+            Some("sk-ci-456")
+        );
+
+        for name in ["EMPTY", "MISSING"] {
+            let result = api_key_from_env(ProviderKind::Anthropic, Some(name), env);
+            assert!(result.is_err());
+            assert!(result.unwrap_err().to_string().contains(name));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_api_key_success() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = TempDir::new()?;
@@ -225,7 +1439,7 @@ mod tests {
         let config_file = config_dir.join("config");
         fs::write(&config_file, "test-api-key-123")?;
 
-        let api_key = read_api_key(config_dir)?;
+        let api_key = read_api_key(config_dir, &EncryptedFileStore::new(config_dir))?;
         assert_eq!(api_key, "test-api-key-123");
 
         Ok(())
@@ -239,7 +1453,7 @@ mod tests {
         let config_file = config_dir.join("config");
         fs::write(&config_file, "  test-api-key-123\n\n")?;
 
-        let api_key = read_api_key(config_dir)?;
+        let api_key = read_api_key(config_dir, &EncryptedFileStore::new(config_dir))?;
         assert_eq!(api_key, "test-api-key-123");
 
         Ok(())
@@ -250,7 +1464,7 @@ mod tests {
         let temp_dir = TempDir::new()?;
         let config_dir = temp_dir.path();
 
-        let result = read_api_key(config_dir);
+        let result = read_api_key(config_dir, &EncryptedFileStore::new(config_dir));
         assert!(result.is_err());
         assert!(
             result
@@ -270,13 +1484,88 @@ mod tests {
         let config_file = config_dir.join("config");
         fs::write(&config_file, "")?;
 
-        let result = read_api_key(config_dir);
+        let result = read_api_key(config_dir, &EncryptedFileStore::new(config_dir));
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("API key is empty"));
 
         Ok(())
     }
 
+    #[test]
+    fn test_store_api_key_keeps_other_settings() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path();
+
+        let config_file = config_dir.join("config");
+        fs::write(&config_file, "provider = \"openai\"\nconventional = true\n")?;
+
+        store_api_key(
+            "test-api-key-123",
+            config_dir,
+            &EncryptedFileStore::new(config_dir),
+        )?;
+
+        let config = Config::load(config_dir)?;
+        assert_eq!(config.api_key, None);
+        assert_eq!(config.provider, ProviderKind::OpenAi);
+        assert!(config.conventional);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_api_key_from_toml_config() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path();
+
+        let config_file = config_dir.join("config");
+        fs::write(&config_file, "api_key = \"test-api-key-123\"\n")?;
+
+        let api_key = read_api_key(config_dir, &EncryptedFileStore::new(config_dir))?;
+        assert_eq!(api_key, "test-api-key-123");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_api_key_moves_plaintext_key_to_store() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path();
+        let store = EncryptedFileStore::new(config_dir);
+
+        let config_file = config_dir.join("config");
+        fs::write(
+            &config_file,
+            "api_key = \"test-api-key-123\"\nconventional = true\n",
+        )?;
+
+        assert_eq!(read_api_key(config_dir, &store)?, "test-api-key-123");
+
+        let content = fs::read_to_string(&config_file)?;
+        assert!(!content.contains("test-api-key-123"));
+        assert!(content.contains("conventional = true"));
+        assert_eq!(store.get()?.as_deref(), Some("test-api-key-123"));
+
+        // Later reads come from the store
+        assert_eq!(read_api_key(config_dir, &store)?, "test-api-key-123");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_api_key_from_store() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path();
+        let store = EncryptedFileStore::new(config_dir);
+
+        fs::write(config_dir.join("config"), "provider = \"openai\"\n")?;
+        store.set("test-api-key-123")?;
+
+        assert_eq!(read_api_key(config_dir, &store)?, "test-api-key-123");
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_api_key_whitespace_only() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = TempDir::new()?;
@@ -285,10 +1574,140 @@ mod tests {
         let config_file = config_dir.join("config");
         fs::write(&config_file, "  \n\n")?;
 
-        let result = read_api_key(config_dir);
+        let result = read_api_key(config_dir, &EncryptedFileStore::new(config_dir));
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("API key is empty"));
 
         Ok(())
     }
+
+    #[test]
+    fn test_dry_run_json() -> Result<(), Box<dyn std::error::Error>> {
+        let elided = vec![diff::Elided {
+            path: "Cargo.lock".to_string(),
+            reason: diff::ElisionReason::Lockfile,
+            additions: 12,
+            deletions: 3,
+        }];
+        let dry_run = DryRun {
+            message: "Add login form",
+            model: "gpt-4o-mini",
+            usage: Some(provider::Usage {
+                input_tokens: 240,
+                output_tokens: 16,
+            }),
+            elided: &elided,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&dry_run)?,
+            serde_json::json!({
+                "message": "Add login form",
+                "model": "gpt-4o-mini",
+                "usage": { "input_tokens": 240, "output_tokens": 16 },
+                "elided": [
+                    { "path": "Cargo.lock", "reason": "lockfile", "additions": 12, "deletions": 3 }
+                ]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_passes_git_args_through() {
+        let cli = Cli::try_parse_from([
+            "gitai",
+            "commit",
+            "-a",
+            "-c",
+            "--",
+            "-S",
+            "--no-verify",
+            "--author=A <a@example.com>",
+        ])
+        .unwrap();
+
+        let Commands::Commit(args) = cli.command else {
+            panic!("expected the commit command");
+        };
+        assert!(args.generation.conventional);
+        assert_eq!(
+            args.git_commit_args(),
+            vec!["--all", "-S", "--no-verify", "--author=A <a@example.com>"]
+        );
+    }
+
+    #[test]
+    fn test_get_changes_all_includes_unstaged_tracked_files()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        let git = |args: &[&str]| Command::new("git").current_dir(repo).args(args).output();
+        git(&["init", "-q"])?;
+        fs::write(repo.join("tracked.txt"), "one\n")?;
+        git(&["add", "tracked.txt"])?;
+
+        // Before the first commit everything in the index is new
+        assert!(get_changes_at(repo, true, false)?.contains("+one"));
+
+        git(&[
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "Initial",
+        ])?;
+        fs::write(repo.join("tracked.txt"), "one\ntwo\n")?;
+        fs::write(repo.join("untracked.txt"), "new\n")?;
+
+        assert_eq!(get_changes_at(repo, false, false)?, "");
+        let all = get_changes_at(repo, true, false)?;
+        assert!(all.contains("+two"));
+        assert!(!all.contains("untracked.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_changes_amend_includes_last_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .current_dir(repo)
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+        };
+        git(&["init", "-q"])?;
+        assert!(get_head_message_at(repo).is_err());
+
+        fs::write(repo.join("first.txt"), "first\n")?;
+        git(&["add", "first.txt"])?;
+        git(&["commit", "-q", "-m", "Add first file"])?;
+
+        // Amending the root commit diffs against nothing
+        assert!(get_changes_at(repo, false, true)?.contains("+first"));
+
+        fs::write(repo.join("second.txt"), "second\n")?;
+        git(&["add", "second.txt"])?;
+        git(&["commit", "-q", "-m", "Add second file\n\nWith a body."])?;
+        fs::write(repo.join("forgotten.txt"), "forgotten\n")?;
+        git(&["add", "forgotten.txt"])?;
+
+        let diff = get_changes_at(repo, false, true)?;
+        assert!(diff.contains("+second"));
+        assert!(diff.contains("+forgotten"));
+        assert!(!diff.contains("+first"));
+        assert_eq!(
+            get_head_message_at(repo)?,
+            "Add second file\n\nWith a body."
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,175 @@
+use std::io::{self, BufRead, Write};
+
+/// What to do with a list of candidate messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    /// Commit with the candidate at this index as it is.
+    Use(usize),
+    /// Open the candidate at this index in the editor before committing.
+    Edit(usize),
+    Regenerate,
+    Abort,
+}
+
+/// Shows the numbered candidates and asks until a valid choice is made.
+/// End of input counts as aborting.
+pub fn choose(
+    candidates: &[String],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Choice> {
+    for (index, candidate) in candidates.iter().enumerate() {
+        let mut lines = candidate.lines();
+        writeln!(
+            output,
+            "{:>2}) {}",
+            index + 1,
+            lines.next().unwrap_or_default()
+        )?;
+        for line in lines {
+            if line.is_empty() {
+                writeln!(output)?;
+            } else {
+                writeln!(output, "    {}", line)?;
+            }
+        }
+        writeln!(output)?;
+    }
+
+    loop {
+        write!(
+            output,
+            "Commit with [1-{}], edit with e<number>, (r)egenerate or (q)uit: ",
+            candidates.len()
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(Choice::Abort);
+        }
+
+        match parse_choice(line.trim(), candidates.len()) {
+            Some(choice) => return Ok(choice),
+            None => writeln!(output, "Invalid choice '{}'", line.trim())?,
+        }
+    }
+}
+
+/// What to do after reading a generated message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feedback {
+    Accept,
+    /// Ask the model for another message, following these instructions.
+    Refine(String),
+    Abort,
+}
+
+/// Asks whether to commit with the message shown or how to change it.
+/// End of input counts as aborting.
+pub fn ask_feedback(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Feedback> {
+    write!(
+        output,
+        "Press Enter to commit, describe what to change, or (q)uit: "
+    )?;
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(Feedback::Abort);
+    }
+
+    Ok(match line.trim() {
+        "" => Feedback::Accept,
+        "q" | "quit" => Feedback::Abort,
+        feedback => Feedback::Refine(feedback.to_string()),
+    })
+}
+
+fn parse_choice(input: &str, count: usize) -> Option<Choice> {
+    let index = |number: &str| {
+        number
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=count).contains(n))
+            .map(|n| n - 1)
+    };
+
+    match input.to_lowercase().as_str() {
+        "r" | "regenerate" => Some(Choice::Regenerate),
+        "q" | "quit" | "a" | "abort" => Some(Choice::Abort),
+        input => match input.strip_prefix('e') {
+            Some(number) => index(number).map(Choice::Edit),
+            None => index(input).map(Choice::Use),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn candidates() -> Vec<String> {
+        vec![
+            "Add login form".to_string(),
+            "Add login form\n\nUsers can now sign in with email.".to_string(),
+        ]
+    }
+
+    fn run(input: &str) -> (Choice, String) {
+        let mut output = Vec::new();
+        let choice = choose(&candidates(), &mut Cursor::new(input), &mut output).unwrap();
+        (choice, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_choose_lists_candidates() {
+        let (choice, output) = run("2\n");
+
+        assert_eq!(choice, Choice::Use(1));
+        assert!(output.starts_with(
+            " 1) Add login form\n\n 2) Add login form\n\n    Users can now sign in with email.\n\n"
+        ));
+        assert!(output.contains("Commit with [1-2]"));
+    }
+
+    #[test]
+    fn test_choose_retries_invalid_input() {
+        let (choice, output) = run("3\nfoo\ne1\n");
+
+        assert_eq!(choice, Choice::Edit(0));
+        assert!(output.contains("Invalid choice '3'"));
+        assert!(output.contains("Invalid choice 'foo'"));
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("1", 3), Some(Choice::Use(0)));
+        assert_eq!(parse_choice("e 3", 3), Some(Choice::Edit(2)));
+        assert_eq!(parse_choice("R", 3), Some(Choice::Regenerate));
+        assert_eq!(parse_choice("q", 3), Some(Choice::Abort));
+        assert_eq!(parse_choice("0", 3), None);
+        assert_eq!(parse_choice("e4", 3), None);
+        assert_eq!(parse_choice("", 3), None);
+    }
+
+    #[test]
+    fn test_ask_feedback() {
+        let ask = |input: &str| ask_feedback(&mut Cursor::new(input), &mut Vec::new()).unwrap();
+
+        assert_eq!(ask("\n"), Feedback::Accept);
+        assert_eq!(
+            ask("  mention the migration \n"),
+            Feedback::Refine("mention the migration".to_string())
+        );
+        assert_eq!(ask("q\n"), Feedback::Abort);
+        assert_eq!(ask(""), Feedback::Abort);
+    }
+
+    #[test]
+    fn test_choose_end_of_input_aborts() {
+        assert_eq!(run("").0, Choice::Abort);
+    }
+}
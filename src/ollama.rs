@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::http::{HttpClient, ReqwestHttpClient};
+
+const BASE_URL: &str = "http://localhost:11434";
+
+pub struct Client<H: HttpClient> {
+    http_client: H,
+    api_base_url: String,
+}
+
+#[derive(Serialize)]
+pub struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+pub struct ChatOptions {
+    num_predict: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct ChatParams {
+    model: String,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    options: ChatOptions,
+}
+
+#[derive(Deserialize)]
+pub struct ChatResponse {
+    pub model: String,
+    pub message: ResponseMessage,
+    pub done: bool,
+    /// Tokens in the prompt, left out when the prompt was cached.
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    pub eval_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ResponseMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatParams {
+    pub fn new(model: String, max_tokens: u64, messages: Vec<ChatMessage>) -> Self {
+        Self {
+            model,
+            messages,
+            stream: false,
+            options: ChatOptions {
+                num_predict: max_tokens,
+                temperature: None,
+            },
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.options.temperature = temperature;
+        self
+    }
+}
+
+impl ChatMessage {
+    pub fn new(content: String) -> Self {
+        Self {
+            role: "user".to_string(),
+            content,
+        }
+    }
+
+    pub fn assistant(content: String) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content,
+        }
+    }
+}
+
+impl Client<ReqwestHttpClient> {
+    pub fn new_default() -> Self {
+        Self::new(ReqwestHttpClient::new())
+    }
+}
+
+impl<H: HttpClient> Client<H> {
+    pub fn new(http_client: H) -> Self {
+        Self {
+            http_client,
+            api_base_url: BASE_URL.to_string(),
+        }
+    }
+
+    pub fn with_base_url(mut self, api_base_url: String) -> Self {
+        self.api_base_url = api_base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub async fn chat(&self, params: ChatParams) -> Result<ChatResponse, Error> {
+        let api_url = format!("{}/api/chat", self.api_base_url);
+        let headers = vec![("content-type".to_string(), "application/json".to_string())];
+        let response = self
+            .http_client
+            .post_json(&api_url, headers, &params)
+            .await?;
+
+        let chat_response: ChatResponse = serde_json::from_str(&response)?;
+        Ok(chat_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    struct MockHttpClient {
+        expected_url: String,
+        response: String,
+        should_fail: bool,
+    }
+
+    #[async_trait]
+    impl HttpClient for MockHttpClient {
+        async fn post_json<T: Serialize + Send + Sync>(
+            &self,
+            url: &str,
+            headers: Vec<(String, String)>,
+            body: &T,
+        ) -> Result<String, Error> {
+            assert_eq!(url, self.expected_url);
+            assert!(
+                !headers.iter().any(|(k, _)| k == "authorization"),
+                "Ollama requests must not carry credentials"
+            );
+
+            let body_json = serde_json::to_value(body)?;
+            assert!(body_json.get("model").is_some());
+            assert!(body_json.get("messages").is_some());
+            assert_eq!(body_json.get("stream"), Some(&json!(false)));
+
+            if self.should_fail {
+                Err(Error::Network("Mock error".to_string()))
+            } else {
+                Ok(self.response.clone())
+            }
+        }
+    }
+
+    fn params() -> ChatParams {
+        ChatParams::new(
+            "llama3.2".to_string(),
+            1024,
+            vec![ChatMessage::new("Test prompt".to_string())],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_chat_success() {
+        let mock_response = json!({
+            "model": "llama3.2",
+            "created_at": "2024-07-22T20:33:28.123648Z",
+            "message": {
+                "role": "assistant",
+                "content": "Add new authentication system"
+            },
+            "done": true
+        })
+        .to_string();
+
+        let mock_client = MockHttpClient {
+            expected_url: "http://localhost:11434/api/chat".to_string(),
+            response: mock_response,
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client);
+
+        let response = client.chat(params()).await.unwrap();
+        assert_eq!(response.model, "llama3.2");
+        assert!(response.done);
+        assert_eq!(response.message.role, "assistant");
+        assert_eq!(response.message.content, "Add new authentication system");
+    }
+
+    #[tokio::test]
+    async fn test_chat_custom_base_url() {
+        let mock_response = json!({
+            "model": "llama3.2",
+            "message": { "role": "assistant", "content": "" },
+            "done": true
+        })
+        .to_string();
+
+        let mock_client = MockHttpClient {
+            expected_url: "http://gpu-box:11434/api/chat".to_string(),
+            response: mock_response,
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client).with_base_url("http://gpu-box:11434/".to_string());
+
+        assert!(client.chat(params()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_chat_http_error() {
+        let mock_client = MockHttpClient {
+            expected_url: "http://localhost:11434/api/chat".to_string(),
+            response: String::new(),
+            should_fail: true,
+        };
+
+        let client = Client::new(mock_client);
+
+        assert!(client.chat(params()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chat_invalid_json_response() {
+        let mock_client = MockHttpClient {
+            expected_url: "http://localhost:11434/api/chat".to_string(),
+            response: "invalid json".to_string(),
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client);
+
+        assert!(client.chat(params()).await.is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::http::{HttpClient, ReqwestHttpClient};
+
+const BASE_URL: &str = "https://api.openai.com/v1";
+
+pub struct Client<H: HttpClient> {
+    http_client: H,
+    api_key: String,
+    api_base_url: String,
+}
+
+#[derive(Serialize)]
+pub struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+pub struct ChatCompletionParams {
+    model: String,
+    max_tokens: u64,
+    messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct ChatCompletion {
+    pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Deserialize)]
+pub struct Choice {
+    pub message: ChoiceMessage,
+}
+
+#[derive(Deserialize)]
+pub struct ChoiceMessage {
+    pub role: String,
+    pub content: Option<String>,
+}
+
+impl ChatCompletionParams {
+    pub fn new(model: String, max_tokens: u64, messages: Vec<ChatMessage>) -> Self {
+        Self {
+            model,
+            max_tokens,
+            messages,
+            temperature: None,
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+impl ChatMessage {
+    pub fn new(content: String) -> Self {
+        Self {
+            role: "user".to_string(),
+            content,
+        }
+    }
+
+    pub fn assistant(content: String) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content,
+        }
+    }
+}
+
+impl Client<ReqwestHttpClient> {
+    pub fn new_default(api_key: String) -> Self {
+        Self::new(ReqwestHttpClient::new(), api_key)
+    }
+}
+
+impl<H: HttpClient> Client<H> {
+    pub fn new(http_client: H, api_key: String) -> Self {
+        Self {
+            http_client,
+            api_key,
+            api_base_url: BASE_URL.to_string(),
+        }
+    }
+
+    pub fn with_base_url(mut self, api_base_url: String) -> Self {
+        self.api_base_url = api_base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub async fn chat_completion(
+        &self,
+        params: ChatCompletionParams,
+    ) -> Result<ChatCompletion, Error> {
+        let api_url = format!("{}/chat/completions", self.api_base_url);
+        let headers = vec![
+            (
+                "authorization".to_string(),
+                format!("Bearer {}", self.api_key),
+            ),
+            ("content-type".to_string(), "application/json".to_string()),
+        ];
+        let response = self
+            .http_client
+            .post_json(&api_url, headers, &params)
+            .await?;
+
+        let completion: ChatCompletion = serde_json::from_str(&response)?;
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    struct MockHttpClient {
+        expected_url: String,
+        expected_headers: Vec<(String, String)>,
+        response: String,
+        should_fail: bool,
+    }
+
+    #[async_trait]
+    impl HttpClient for MockHttpClient {
+        async fn post_json<T: Serialize + Send + Sync>(
+            &self,
+            url: &str,
+            headers: Vec<(String, String)>,
+            body: &T,
+        ) -> Result<String, Error> {
+            assert_eq!(url, self.expected_url);
+
+            for (key, value) in &self.expected_headers {
+                assert!(
+                    headers.iter().any(|(k, v)| k == key && v == value),
+                    "Missing header: {:?}={:?}",
+                    key,
+                    value
+                )
+            }
+
+            let body_json = serde_json::to_value(body)?;
+            assert!(body_json.get("model").is_some());
+            assert!(body_json.get("messages").is_some());
+
+            if self.should_fail {
+                Err(Error::Network("Mock error".to_string()))
+            } else {
+                Ok(self.response.clone())
+            }
+        }
+    }
+
+    fn params() -> ChatCompletionParams {
+        ChatCompletionParams::new(
+            "gpt-4o-mini".to_string(),
+            1024,
+            vec![ChatMessage::new("Test prompt".to_string())],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_success() {
+        let mock_response = json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "Add new authentication system"
+                },
+                "finish_reason": "stop"
+            }]
+        })
+        .to_string();
+
+        let mock_client = MockHttpClient {
+            expected_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            expected_headers: vec![("authorization".to_string(), "Bearer test_key".to_string())],
+            response: mock_response,
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let completion = client.chat_completion(params()).await.unwrap();
+        assert_eq!(completion.choices.len(), 1);
+        assert_eq!(completion.choices[0].message.role, "assistant");
+        assert_eq!(
+            completion.choices[0].message.content.as_deref(),
+            Some("Add new authentication system")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_custom_base_url() {
+        let mock_response = json!({ "choices": [] }).to_string();
+
+        let mock_client = MockHttpClient {
+            expected_url: "https://gateway.example.com/openai/v1/chat/completions".to_string(),
+            expected_headers: vec![],
+            response: mock_response,
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client, "test_key".to_string())
+            .with_base_url("https://gateway.example.com/openai/v1/".to_string());
+
+        let completion = client.chat_completion(params()).await.unwrap();
+        assert!(completion.choices.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_http_error() {
+        let mock_client = MockHttpClient {
+            expected_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            expected_headers: vec![],
+            response: String::new(),
+            should_fail: true,
+        };
+
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let result = client.chat_completion(params()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_invalid_json_response() {
+        let mock_client = MockHttpClient {
+            expected_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            expected_headers: vec![],
+            response: "invalid json".to_string(),
+            should_fail: false,
+        };
+
+        let client = Client::new(mock_client, "test_key".to_string());
+
+        let result = client.chat_completion(params()).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use crate::git::{git_at, messages_at};
+
+// Tried in order when the remote does not say which branch is the default
+const BASE_BRANCHES: &[&str] = &["main", "master"];
+
+/// The current branch compared to the branch it will be merged into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub commits: Vec<String>,
+    pub diff: String,
+}
+
+/// The remote's default branch, or else a local `main` or `master`.
+pub fn default_base_at(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(remote_head) = git_at(
+        path,
+        &[
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            "refs/remotes/origin/HEAD",
+        ],
+    ) {
+        return Ok(remote_head.trim().to_string());
+    }
+
+    BASE_BRANCHES
+        .iter()
+        .find(|branch| git_at(path, &["rev-parse", "--verify", "--quiet", branch]).is_ok())
+        .map(|branch| branch.to_string())
+        .ok_or_else(|| "Could not find the base branch, pass it with --base".into())
+}
+
+/// The commits and changes on HEAD since it forked from `base`.
+pub fn branch_at(path: &Path, base: &str) -> Result<Branch, Box<dyn std::error::Error>> {
+    let merge_base = git_at(path, &["merge-base", base, "HEAD"])?;
+    let merge_base = merge_base.trim();
+
+    let commits = messages_at(path, &format!("{}..HEAD", merge_base))?;
+    if commits.is_empty() {
+        return Err(format!("No commits on the current branch since '{}'", base).into());
+    }
+
+    Ok(Branch {
+        commits,
+        diff: git_at(path, &["diff", merge_base, "HEAD"])?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit(repo: &Path, file: &str, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(repo.join(file), message)?;
+        git_at(repo, &["add", file])?;
+        git_at(
+            repo,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                message,
+            ],
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_since_base() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        git_at(repo, &["init", "-q", "-b", "main"])?;
+        commit(repo, "readme", "Initial")?;
+        git_at(repo, &["checkout", "-q", "-b", "feature"])?;
+        commit(repo, "parser", "Add parser")?;
+        commit(repo, "tests", "Add parser tests")?;
+        // Commits on the base after the fork are not part of the branch
+        git_at(repo, &["checkout", "-q", "main"])?;
+        commit(repo, "changelog", "Update changelog")?;
+        git_at(repo, &["checkout", "-q", "feature"])?;
+
+        assert_eq!(default_base_at(repo)?, "main");
+
+        let branch = branch_at(repo, "main")?;
+        assert_eq!(branch.commits, vec!["Add parser", "Add parser tests"]);
+        assert!(branch.diff.contains("+Add parser tests"));
+        assert!(!branch.diff.contains("changelog"));
+
+        git_at(repo, &["checkout", "-q", "main"])?;
+        assert!(branch_at(repo, "main").is_err());
+
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::conventional::TYPES;
+
 // From https://tbaggery.com/2008/04/19/a-note-about-git-commit-messages.html
 const GIT_COMMIT_BEST_PRACTICES: &str = r#"
 Capitalized, short (50 chars or less) summary
@@ -54,8 +56,29 @@ Examples:
 Analyze this git diff and suggest an appropriate conventional commit message:
 "#;
 
-pub fn build_prompt(diff: &str, use_conventional: bool) -> String {
-    let commit_message_rules = if use_conventional {
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    pub conventional: bool,
+    pub language: Option<String>,
+    /// Conventional commit scopes the project allows.
+    pub scopes: Vec<String>,
+    /// Changes left out of the diff, one line each.
+    pub omitted: Vec<String>,
+    /// The diff was too large and is given as summaries of its parts.
+    pub summarized: bool,
+    /// The current message of the commit being amended.
+    pub previous_message: Option<String>,
+}
+
+pub fn build_prompt(diff: &str, options: &PromptOptions) -> String {
+    let commit_message_rules = if options.conventional && !options.scopes.is_empty() {
+        format!(
+            "{}\n\n{}\nThis project only allows these scopes: {}. Omit the scope if none of them fit.\n",
+            GIT_COMMIT_BEST_PRACTICES,
+            CONVENTIONAL_COMMIT_ADDITIONAL_RULES,
+            options.scopes.join(", ")
+        )
+    } else if options.conventional {
         format!(
             "{}\n\n{}",
             GIT_COMMIT_BEST_PRACTICES, CONVENTIONAL_COMMIT_ADDITIONAL_RULES
@@ -64,6 +87,40 @@ pub fn build_prompt(diff: &str, use_conventional: bool) -> String {
         GIT_COMMIT_BEST_PRACTICES.to_string()
     };
 
+    let language_instruction = match &options.language {
+        Some(language) => format!("\nWrite the commit message in {}.", language),
+        None => String::new(),
+    };
+
+    let omitted_changes = if options.omitted.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\nThese changes are also staged but were left out of the diff:\n\n<omitted_changes>\n{}\n</omitted_changes>\n",
+            options.omitted.join("\n")
+        )
+    };
+
+    let previous_message = match &options.previous_message {
+        Some(message) => format!(
+            "\nThese changes amend an existing commit. Keep what still applies from its current message:\n\n<previous_message>\n{}\n</previous_message>\n",
+            message.trim()
+        ),
+        None => String::new(),
+    };
+
+    let changes = if options.summarized {
+        format!(
+            "The staged changes are too large to show in full. Here are summaries of each part of the diff:\n\n<change_summaries>\n{}\n</change_summaries>",
+            diff
+        )
+    } else {
+        format!(
+            "Here are the staged changes to analyze:\n\n<git_diff>\n{}\n</git_diff>",
+            diff
+        )
+    };
+
     format!(
         r#"
 You are a helpful assistant that generates git commit messages based on code changes.
@@ -74,19 +131,162 @@ Please analyze the following git diff and generate a commit message that follows
 {}
 </commit_message_rules>
 
-Here are the staged changes to analyze:
-
-<git_diff>
 {}
-</git_diff>
-
+{}
 Generate a clear, concise commit message for these changes.
 Focus on the "why" and "what" of the changes, not just the "how".
 If the changes are simple and self-explanatory, a single line summary is sufficient.
+{}
+Do not include any other text in your response.
+    "#,
+        commit_message_rules,
+        changes,
+        omitted_changes + &previous_message,
+        language_instruction
+    )
+}
+
+/// Asks for the title and Markdown description of a pull request made of
+/// `commits`, given as full messages, and their combined `diff`.
+pub fn build_pr_prompt(diff: &str, commits: &[String], options: &PromptOptions) -> String {
+    let changes = if options.summarized {
+        format!(
+            "The changes are too large to show in full. Here are summaries of each part of the diff:\n\n<change_summaries>\n{}\n</change_summaries>",
+            diff
+        )
+    } else {
+        format!(
+            "Here are the changes compared to the base branch:\n\n<git_diff>\n{}\n</git_diff>",
+            diff
+        )
+    };
+
+    let omitted_changes = if options.omitted.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\nThese changes are also part of the branch but were left out of the diff:\n\n<omitted_changes>\n{}\n</omitted_changes>\n",
+            options.omitted.join("\n")
+        )
+    };
+
+    let title_instruction = if options.conventional {
+        " Write it like a conventional commit subject, such as \"feat(auth): add OAuth2 login\"."
+    } else {
+        ""
+    };
+
+    let language_instruction = match &options.language {
+        Some(language) => format!("\nWrite the title and description in {}.", language),
+        None => String::new(),
+    };
+
+    format!(
+        r#"
+You are a helpful assistant that writes pull request descriptions based on code changes.
+
+Here are the messages of the commits in the pull request, oldest first:
+
+<commits>
+{}
+</commits>
+
+{}
+{}
+Respond with a title on the first line, a blank line, and then a description in Markdown with these sections:
+
+## Summary
+What the pull request does and why, in a few sentences.
+
+## Changes
+A bullet list of the notable changes.
+
+## Testing
+How the changes were tested, based on the tests in the diff and the commit messages, or what a reviewer could check by hand.
+
+Keep the title under 72 characters.{}
+{}
+Do not include any other text in your response.
+    "#,
+        commits.join("\n---\n"),
+        changes,
+        omitted_changes,
+        title_instruction,
+        language_instruction
+    )
+}
+
+/// Asks which conventional commit type fits each of the numbered commit
+/// messages, for commits that were not written in that format.
+pub fn build_classification_prompt(messages: &[String]) -> String {
+    let commits = messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| format!("{}. {}", index + 1, message.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        r#"
+You are helping to write a changelog from a project's commit history.
+Classify each of these commits by the conventional commit type that fits it best:
+
+<commits>
+{}
+</commits>
+
+The types are: {}.
+Use 'feat' for new features, 'fix' for bug fixes, 'perf' for performance improvements and 'refactor' for code changes that neither fix a bug nor add a feature.
+
+Answer with one line per commit in the form "<number>: <type>", for example "1: feat".
+Do not include any other text in your response.
+    "#,
+        commits,
+        TYPES.join(", ")
+    )
+}
+
+/// Follows up on a message that broke the rules, in the same conversation
+/// as the prompt it answered.
+pub fn build_repair_prompt(errors: &[String]) -> String {
+    let errors = errors
+        .iter()
+        .map(|error| format!("- {}", error))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"
+That commit message does not follow the conventional commit rules:
+
+<errors>
+{}
+</errors>
 
+Write the commit message again, fixing these errors.
 Do not include any other text in your response.
     "#,
-        commit_message_rules, diff
+        errors
+    )
+}
+
+/// Asks for a summary of one part of a diff too large for a single prompt.
+pub fn build_summary_prompt(diff: &str, part: usize, parts: usize) -> String {
+    format!(
+        r#"
+You are helping to write a git commit message for a change that is too large to review at once.
+This is part {} of {} of the staged diff:
+
+<git_diff>
+{}
+</git_diff>
+
+Summarize what changed in this part in a few short bullet points.
+Name the files, functions and types involved, and mention the intent of the change where it is apparent.
+Do not write a commit message.
+Do not include any other text in your response.
+    "#,
+        part, parts, diff
     )
 }
 
@@ -108,7 +308,7 @@ index 1234567..abcdefg 100644
  }
         "#;
 
-        let prompt = build_prompt(diff, false);
+        let prompt = build_prompt(diff, &PromptOptions::default());
 
         assert!(prompt.contains(
             "You are a helpful assistant that generates git commit messages based on code changes"
@@ -137,7 +337,11 @@ index 1234567..abcdefg 100644
  }
         "#;
 
-        let prompt = build_prompt(diff, true);
+        let options = PromptOptions {
+            conventional: true,
+            ..PromptOptions::default()
+        };
+        let prompt = build_prompt(diff, &options);
 
         assert!(prompt.contains(
             "You are a helpful assistant that generates git commit messages based on code changes"
@@ -151,4 +355,178 @@ index 1234567..abcdefg 100644
 
         Ok(())
     }
+
+    #[test]
+    fn test_build_prompt_language() -> Result<(), Box<dyn std::error::Error>> {
+        let options = PromptOptions {
+            language: Some("German".to_string()),
+            ..PromptOptions::default()
+        };
+
+        let prompt = build_prompt("diff", &options);
+        assert!(prompt.contains("Write the commit message in German."));
+
+        let prompt = build_prompt("diff", &PromptOptions::default());
+        assert!(!prompt.contains("Write the commit message in"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_prompt_conventional_scopes() -> Result<(), Box<dyn std::error::Error>> {
+        let options = PromptOptions {
+            conventional: true,
+            scopes: vec!["api".to_string(), "web".to_string()],
+            ..PromptOptions::default()
+        };
+
+        let prompt = build_prompt("diff", &options);
+        assert!(prompt.contains(CONVENTIONAL_COMMIT_ADDITIONAL_RULES));
+        assert!(prompt.contains("This project only allows these scopes: api, web."));
+
+        // Scopes only apply to conventional commits
+        let options = PromptOptions {
+            conventional: false,
+            ..options
+        };
+        let prompt = build_prompt("diff", &options);
+        assert!(!prompt.contains("This project only allows these scopes"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_prompt_omitted_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let prompt = build_prompt("diff", &PromptOptions::default());
+        assert!(!prompt.contains("<omitted_changes>"));
+
+        let options = PromptOptions {
+            omitted: vec![
+                "Cargo.lock | +12 -3 (lockfile)".to_string(),
+                "src/big.rs | +900 -20 (too large)".to_string(),
+            ],
+            ..PromptOptions::default()
+        };
+
+        let prompt = build_prompt("diff", &options);
+        assert!(prompt.contains(
+            "<omitted_changes>\nCargo.lock | +12 -3 (lockfile)\nsrc/big.rs | +900 -20 (too large)\n</omitted_changes>"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_prompt_previous_message() -> Result<(), Box<dyn std::error::Error>> {
+        let prompt = build_prompt("diff", &PromptOptions::default());
+        assert!(!prompt.contains("<previous_message>"));
+
+        let options = PromptOptions {
+            previous_message: Some("Add login form\n\n".to_string()),
+            ..PromptOptions::default()
+        };
+
+        let prompt = build_prompt("diff", &options);
+        assert!(prompt.contains("These changes amend an existing commit."));
+        assert!(prompt.contains("<previous_message>\nAdd login form\n</previous_message>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_prompt_summarized() -> Result<(), Box<dyn std::error::Error>> {
+        let options = PromptOptions {
+            summarized: true,
+            ..PromptOptions::default()
+        };
+
+        let prompt = build_prompt("Part 1:\n- Add parser", &options);
+        assert!(prompt.contains("<change_summaries>\nPart 1:\n- Add parser\n</change_summaries>"));
+        assert!(!prompt.contains("<git_diff>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_pr_prompt() -> Result<(), Box<dyn std::error::Error>> {
+        let commits = vec![
+            "Add parser\n\nWith a body.".to_string(),
+            "Add parser tests".to_string(),
+        ];
+
+        let prompt = build_pr_prompt("diff --git a/a b/a", &commits, &PromptOptions::default());
+        assert!(
+            prompt.contains(
+                "<commits>\nAdd parser\n\nWith a body.\n---\nAdd parser tests\n</commits>"
+            )
+        );
+        assert!(prompt.contains("<git_diff>\ndiff --git a/a b/a\n</git_diff>"));
+        assert!(prompt.contains("## Summary"));
+        assert!(prompt.contains("## Testing"));
+        assert!(!prompt.contains("conventional commit subject"));
+
+        let options = PromptOptions {
+            conventional: true,
+            summarized: true,
+            language: Some("German".to_string()),
+            ..PromptOptions::default()
+        };
+        let prompt = build_pr_prompt("Part 1:\n- Add parser", &commits, &options);
+        assert!(prompt.contains("<change_summaries>\nPart 1:\n- Add parser\n</change_summaries>"));
+        assert!(prompt.contains("conventional commit subject"));
+        assert!(prompt.contains("Write the title and description in German."));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conventional_rules_list_the_known_types() {
+        assert!(
+            CONVENTIONAL_COMMIT_ADDITIONAL_RULES
+                .contains(&format!("The type must be one of: {}\n", TYPES.join(", ")))
+        );
+    }
+
+    #[test]
+    fn test_build_classification_prompt() -> Result<(), Box<dyn std::error::Error>> {
+        let messages = vec![
+            "Add login form\n\nWith a body.".to_string(),
+            "Speed up the parser".to_string(),
+        ];
+
+        let prompt = build_classification_prompt(&messages);
+        assert!(prompt.contains(
+            "<commits>\n1. Add login form\n\nWith a body.\n\n2. Speed up the parser\n</commits>"
+        ));
+        assert!(prompt.contains("The types are: feat, fix, docs,"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_repair_prompt() -> Result<(), Box<dyn std::error::Error>> {
+        let prompt = build_repair_prompt(&[
+            "Unknown type 'feature'".to_string(),
+            "The description is empty".to_string(),
+        ]);
+
+        assert!(
+            prompt.contains(
+                "<errors>\n- Unknown type 'feature'\n- The description is empty\n</errors>"
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_summary_prompt() -> Result<(), Box<dyn std::error::Error>> {
+        let prompt = build_summary_prompt("diff --git a/a b/a", 2, 5);
+
+        assert!(prompt.contains("This is part 2 of 5 of the staged diff"));
+        assert!(prompt.contains("<git_diff>\ndiff --git a/a b/a\n</git_diff>"));
+        assert!(prompt.contains("Do not write a commit message."));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,662 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::anthropic;
+use crate::error::Error;
+use crate::http::{HttpClient, ReqwestHttpClient};
+use crate::ollama;
+use crate::openai;
+use crate::retry::{RetryPolicy, RetryingHttpClient};
+
+const ANTHROPIC_MODEL: &str = "claude-3-5-sonnet-20240620";
+const OPENAI_MODEL: &str = "gpt-4o-mini";
+const OLLAMA_MODEL: &str = "llama3.2";
+const MAX_TOKENS: u64 = 1000;
+
+pub type TextCallback<'a> = dyn FnMut(&str) + Send + 'a;
+
+/// Who a message in a conversation comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One turn of a conversation with the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: String) -> Self {
+        Self {
+            role: Role::User,
+            content,
+        }
+    }
+
+    pub fn assistant(content: String) -> Self {
+        Self {
+            role: Role::Assistant,
+            content,
+        }
+    }
+}
+
+/// Tokens spent on requests, as reported by the provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+// Adds up the usage of every request a provider makes, several when the diff
+// is summarized first or more than one message is generated
+#[derive(Default)]
+struct UsageCounter(Mutex<Option<Usage>>);
+
+impl UsageCounter {
+    fn record(&self, usage: Option<Usage>) {
+        if let Some(usage) = usage {
+            let mut total = self.0.lock().unwrap();
+            let total = total.get_or_insert_default();
+            total.input_tokens += usage.input_tokens;
+            total.output_tokens += usage.output_tokens;
+        }
+    }
+
+    fn total(&self) -> Option<Usage> {
+        *self.0.lock().unwrap()
+    }
+}
+
+impl From<anthropic::Usage> for Usage {
+    fn from(usage: anthropic::Usage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
+}
+
+impl From<openai::Usage> for Usage {
+    fn from(usage: openai::Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+/// A backend able to continue a conversation, usually a single rendered
+/// prompt.
+#[async_trait::async_trait]
+pub trait Provider: Send + Sync {
+    async fn chat(&self, messages: &[Message]) -> Result<String, Error>;
+
+    /// Like `chat`, but reports text through `on_text` as it is generated.
+    /// Providers without streaming support report it all at once.
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        on_text: &mut TextCallback<'_>,
+    ) -> Result<String, Error> {
+        let text = self.chat(messages).await?;
+        on_text(&text);
+        Ok(text)
+    }
+
+    async fn complete(&self, prompt: &str) -> Result<String, Error> {
+        self.chat(&[Message::user(prompt.to_string())]).await
+    }
+
+    async fn complete_stream(
+        &self,
+        prompt: &str,
+        on_text: &mut TextCallback<'_>,
+    ) -> Result<String, Error> {
+        self.chat_stream(&[Message::user(prompt.to_string())], on_text)
+            .await
+    }
+
+    /// Tokens used by all requests so far, if the provider reports them.
+    fn usage(&self) -> Option<Usage> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Anthropic,
+    OpenAi,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::Anthropic => ANTHROPIC_MODEL,
+            ProviderKind::OpenAi => OPENAI_MODEL,
+            ProviderKind::Ollama => OLLAMA_MODEL,
+        }
+    }
+
+    /// Local providers run on this machine and need no credentials.
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, ProviderKind::Ollama)
+    }
+
+    /// The environment variable the provider's own tooling reads the API
+    /// key from.
+    pub fn api_key_env(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderKind::OpenAi => Some("OPENAI_API_KEY"),
+            ProviderKind::Ollama => None,
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderKind::Anthropic => write!(f, "anthropic"),
+            ProviderKind::OpenAi => write!(f, "openai"),
+            ProviderKind::Ollama => write!(f, "ollama"),
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "anthropic" => Ok(ProviderKind::Anthropic),
+            "openai" => Ok(ProviderKind::OpenAi),
+            "ollama" => Ok(ProviderKind::Ollama),
+            other => Err(format!(
+                "Unknown provider '{}'. Expected 'anthropic', 'openai' or 'ollama'.",
+                other
+            )),
+        }
+    }
+}
+
+/// Which provider to talk to and how, see `Config::provider_settings`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProviderSettings {
+    pub kind: ProviderKind,
+    pub model: Option<String>,
+    pub base_url: Option<String>,
+    pub max_tokens: Option<u64>,
+    pub temperature: Option<f32>,
+    pub max_attempts: Option<u32>,
+}
+
+impl ProviderSettings {
+    pub fn model(&self) -> &str {
+        self.model
+            .as_deref()
+            .unwrap_or_else(|| self.kind.default_model())
+    }
+}
+
+pub struct AnthropicProvider<H: HttpClient> {
+    client: anthropic::Client<H>,
+    model: String,
+    max_tokens: u64,
+    temperature: Option<f32>,
+    usage: UsageCounter,
+}
+
+impl<H: HttpClient> AnthropicProvider<H> {
+    pub fn new(client: anthropic::Client<H>, model: String, max_tokens: u64) -> Self {
+        Self {
+            client,
+            model,
+            max_tokens,
+            temperature: None,
+            usage: UsageCounter::default(),
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    fn params(&self, messages: &[Message]) -> anthropic::MessageNewParams {
+        let messages = messages
+            .iter()
+            .map(|message| match message.role {
+                Role::User => anthropic::MessageParam::new(message.content.clone()),
+                Role::Assistant => anthropic::MessageParam::assistant(message.content.clone()),
+            })
+            .collect();
+        anthropic::MessageNewParams::new(self.model.clone(), self.max_tokens, messages)
+            .with_temperature(self.temperature)
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: HttpClient> Provider for AnthropicProvider<H> {
+    async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+        let params = self.params(messages);
+        let message = self.client.new_message(params).await?;
+        self.usage.record(message.usage.map(Usage::from));
+
+        match message.content.first() {
+            Some(content) => Ok(content.text.clone()),
+            None => Err(Error::Decode(
+                "Anthropic API returned an empty response".to_string(),
+            )),
+        }
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        on_text: &mut TextCallback<'_>,
+    ) -> Result<String, Error> {
+        let params = self.params(messages);
+        let message = self.client.new_message_stream(params, on_text).await?;
+        self.usage.record(message.usage.map(Usage::from));
+
+        match message.content.first() {
+            Some(content) if !content.text.is_empty() => Ok(content.text.clone()),
+            _ => Err(Error::Decode(
+                "Anthropic API returned an empty response".to_string(),
+            )),
+        }
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.usage.total()
+    }
+}
+
+pub struct OpenAiProvider<H: HttpClient> {
+    client: openai::Client<H>,
+    model: String,
+    max_tokens: u64,
+    temperature: Option<f32>,
+    usage: UsageCounter,
+}
+
+impl<H: HttpClient> OpenAiProvider<H> {
+    pub fn new(client: openai::Client<H>, model: String, max_tokens: u64) -> Self {
+        Self {
+            client,
+            model,
+            max_tokens,
+            temperature: None,
+            usage: UsageCounter::default(),
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: HttpClient> Provider for OpenAiProvider<H> {
+    async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+        let messages = messages
+            .iter()
+            .map(|message| match message.role {
+                Role::User => openai::ChatMessage::new(message.content.clone()),
+                Role::Assistant => openai::ChatMessage::assistant(message.content.clone()),
+            })
+            .collect();
+        let params =
+            openai::ChatCompletionParams::new(self.model.clone(), self.max_tokens, messages)
+                .with_temperature(self.temperature);
+        let completion = self.client.chat_completion(params).await?;
+        self.usage.record(completion.usage.map(Usage::from));
+
+        match completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+        {
+            Some(content) => Ok(content),
+            None => Err(Error::Decode(
+                "OpenAI API returned an empty response".to_string(),
+            )),
+        }
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.usage.total()
+    }
+}
+
+pub struct OllamaProvider<H: HttpClient> {
+    client: ollama::Client<H>,
+    model: String,
+    max_tokens: u64,
+    temperature: Option<f32>,
+    usage: UsageCounter,
+}
+
+impl<H: HttpClient> OllamaProvider<H> {
+    pub fn new(client: ollama::Client<H>, model: String, max_tokens: u64) -> Self {
+        Self {
+            client,
+            model,
+            max_tokens,
+            temperature: None,
+            usage: UsageCounter::default(),
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: HttpClient> Provider for OllamaProvider<H> {
+    async fn chat(&self, messages: &[Message]) -> Result<String, Error> {
+        let messages = messages
+            .iter()
+            .map(|message| match message.role {
+                Role::User => ollama::ChatMessage::new(message.content.clone()),
+                Role::Assistant => ollama::ChatMessage::assistant(message.content.clone()),
+            })
+            .collect();
+        let params = ollama::ChatParams::new(self.model.clone(), self.max_tokens, messages)
+            .with_temperature(self.temperature);
+        let response = self.client.chat(params).await?;
+        if let Some(output_tokens) = response.eval_count {
+            self.usage.record(Some(Usage {
+                input_tokens: response.prompt_eval_count.unwrap_or_default(),
+                output_tokens,
+            }));
+        }
+
+        if response.message.content.trim().is_empty() {
+            return Err(Error::Decode(
+                "Ollama returned an empty response".to_string(),
+            ));
+        }
+
+        Ok(response.message.content)
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.usage.total()
+    }
+}
+
+fn http_client(settings: &ProviderSettings) -> RetryingHttpClient<ReqwestHttpClient> {
+    let mut policy = RetryPolicy::default();
+    if let Some(max_attempts) = settings.max_attempts {
+        policy = policy.with_max_attempts(max_attempts);
+    }
+
+    RetryingHttpClient::new(ReqwestHttpClient::new(), policy).with_notifier(Box::new(
+        |error, attempt, delay| {
+            eprintln!(
+                "{} Retrying in {:.1}s (attempt {} failed)...",
+                error,
+                delay.as_secs_f64(),
+                attempt
+            );
+        },
+    ))
+}
+
+pub fn build_provider(settings: &ProviderSettings, api_key: &str) -> Box<dyn Provider> {
+    let model = settings.model().to_string();
+    let max_tokens = settings.max_tokens.unwrap_or(MAX_TOKENS);
+    let http_client = http_client(settings);
+
+    match settings.kind {
+        ProviderKind::Anthropic => {
+            let mut client = anthropic::Client::new(http_client, api_key.to_string());
+            if let Some(base_url) = &settings.base_url {
+                client = client.with_base_url(base_url.clone());
+            }
+            Box::new(
+                AnthropicProvider::new(client, model, max_tokens)
+                    .with_temperature(settings.temperature),
+            )
+        }
+        ProviderKind::OpenAi => {
+            let mut client = openai::Client::new(http_client, api_key.to_string());
+            if let Some(base_url) = &settings.base_url {
+                client = client.with_base_url(base_url.clone());
+            }
+            Box::new(
+                OpenAiProvider::new(client, model, max_tokens)
+                    .with_temperature(settings.temperature),
+            )
+        }
+        ProviderKind::Ollama => {
+            let mut client = ollama::Client::new(http_client);
+            if let Some(base_url) = &settings.base_url {
+                client = client.with_base_url(base_url.clone());
+            }
+            Box::new(
+                OllamaProvider::new(client, model, max_tokens)
+                    .with_temperature(settings.temperature),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use serde_json::json;
+
+    struct MockHttpClient {
+        response: String,
+    }
+
+    #[async_trait::async_trait]
+    impl HttpClient for MockHttpClient {
+        async fn post_json<T: Serialize + Send + Sync>(
+            &self,
+            _url: &str,
+            _headers: Vec<(String, String)>,
+            _body: &T,
+        ) -> Result<String, Error> {
+            Ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn test_settings_model_defaults_per_provider() {
+        let mut settings = ProviderSettings::default();
+        assert_eq!(settings.model(), ANTHROPIC_MODEL);
+
+        settings.kind = ProviderKind::Ollama;
+        assert_eq!(settings.model(), OLLAMA_MODEL);
+        assert!(!settings.kind.requires_api_key());
+        assert!(ProviderKind::Anthropic.requires_api_key());
+        assert_eq!(ProviderKind::Ollama.api_key_env(), None);
+        assert_eq!(
+            ProviderKind::Anthropic.api_key_env(),
+            Some("ANTHROPIC_API_KEY")
+        );
+
+        settings.model = Some("qwen2.5-coder".to_string());
+        assert_eq!(settings.model(), "qwen2.5-coder");
+    }
+
+    #[test]
+    fn test_provider_kind_from_str() {
+        assert_eq!("OpenAI".parse(), Ok(ProviderKind::OpenAi));
+        assert_eq!(" ollama ".parse(), Ok(ProviderKind::Ollama));
+
+        let result = "mistral".parse::<ProviderKind>();
+        assert!(result.unwrap_err().contains("Unknown provider 'mistral'"));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_provider_complete() -> Result<(), Box<dyn std::error::Error>> {
+        let http_client = MockHttpClient {
+            response: json!({ "content": [{ "type": "text", "text": "Add login form" }] })
+                .to_string(),
+        };
+        let client = anthropic::Client::new(http_client, "test_key".to_string());
+        let provider = AnthropicProvider::new(client, ANTHROPIC_MODEL.to_string(), 1000);
+
+        assert_eq!(provider.complete("prompt").await?, "Add login form");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anthropic_provider_keeps_conversation_roles() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let client = anthropic::Client::new(
+            MockHttpClient {
+                response: String::new(),
+            },
+            "test_key".to_string(),
+        );
+        let provider = AnthropicProvider::new(client, ANTHROPIC_MODEL.to_string(), 1000);
+
+        let params = provider.params(&[
+            Message::user("prompt".to_string()),
+            Message::assistant("Add login form".to_string()),
+            Message::user("shorter".to_string()),
+        ]);
+
+        assert_eq!(
+            serde_json::to_value(params)?["messages"],
+            json!([
+                { "role": "user", "content": "prompt" },
+                { "role": "assistant", "content": "Add login form" },
+                { "role": "user", "content": "shorter" }
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_provider_empty_response() {
+        let http_client = MockHttpClient {
+            response: json!({ "content": [] }).to_string(),
+        };
+        let client = anthropic::Client::new(http_client, "test_key".to_string());
+        let provider = AnthropicProvider::new(client, ANTHROPIC_MODEL.to_string(), 1000);
+
+        assert!(provider.complete("prompt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_complete_stream_reports_whole_text()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let http_client = MockHttpClient {
+            response: json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Add login form" } }]
+            })
+            .to_string(),
+        };
+        let client = openai::Client::new(http_client, "test_key".to_string());
+        let provider = OpenAiProvider::new(client, OPENAI_MODEL.to_string(), 1000);
+
+        let mut received = Vec::new();
+        let text = provider
+            .complete_stream("prompt", &mut |text| received.push(text.to_string()))
+            .await?;
+
+        assert_eq!(text, "Add login form");
+        assert_eq!(received, vec!["Add login form"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_openai_provider_complete() -> Result<(), Box<dyn std::error::Error>> {
+        let http_client = MockHttpClient {
+            response: json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Add login form" } }]
+            })
+            .to_string(),
+        };
+        let client = openai::Client::new(http_client, "test_key".to_string());
+        let provider = OpenAiProvider::new(client, OPENAI_MODEL.to_string(), 1000);
+
+        assert_eq!(provider.complete("prompt").await?, "Add login form");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_provider_adds_up_usage() -> Result<(), Box<dyn std::error::Error>> {
+        let http_client = MockHttpClient {
+            response: json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Add login form" } }],
+                "usage": { "prompt_tokens": 120, "completion_tokens": 8, "total_tokens": 128 }
+            })
+            .to_string(),
+        };
+        let client = openai::Client::new(http_client, "test_key".to_string());
+        let provider = OpenAiProvider::new(client, OPENAI_MODEL.to_string(), 1000);
+        assert_eq!(provider.usage(), None);
+
+        provider.complete("summary").await?;
+        provider.complete("prompt").await?;
+
+        assert_eq!(
+            provider.usage(),
+            Some(Usage {
+                input_tokens: 240,
+                output_tokens: 16
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ollama_provider_complete() -> Result<(), Box<dyn std::error::Error>> {
+        let http_client = MockHttpClient {
+            response: json!({
+                "model": OLLAMA_MODEL,
+                "message": { "role": "assistant", "content": "Add login form" },
+                "done": true
+            })
+            .to_string(),
+        };
+        let client = ollama::Client::new(http_client);
+        let provider = OllamaProvider::new(client, OLLAMA_MODEL.to_string(), 1000);
+
+        assert_eq!(provider.complete("prompt").await?, "Add login form");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_openai_provider_null_content() {
+        let http_client = MockHttpClient {
+            response: json!({
+                "choices": [{ "message": { "role": "assistant", "content": null } }]
+            })
+            .to_string(),
+        };
+        let client = openai::Client::new(http_client, "test_key".to_string());
+        let provider = OpenAiProvider::new(client, OPENAI_MODEL.to_string(), 1000);
+
+        assert!(provider.complete("prompt").await.is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::conventional::ConventionalCommit;
+use crate::lint::{self, Rules, Severity};
+
+const BODY_WIDTH: usize = 72;
+
+// Lines such as "Here is a commit message:" or "**Commit message:**" that
+// models put before the message
+static PREAMBLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^((sure|certainly|okay|ok)[!.,].*|(here|below)\b.*:|.*commit message.*:)$")
+        .unwrap()
+});
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)([-*]|\d+[.)])\s+").unwrap());
+
+/// Cleans up a message as the model wrote it: preambles and code fences
+/// around it are removed and overlong body lines are wrapped.
+pub fn repair(text: &str) -> String {
+    let mut lines: Vec<&str> = text.trim().lines().collect();
+    strip_preamble(&mut lines);
+
+    if lines
+        .first()
+        .is_some_and(|line| line.trim_start().starts_with("```"))
+    {
+        // Anything after the closing fence is commentary on the message
+        let end = lines[1..]
+            .iter()
+            .position(|line| line.trim_start().starts_with("```"))
+            .map_or(lines.len(), |index| index + 1);
+        lines = lines[1..end].to_vec();
+        strip_preamble(&mut lines);
+    }
+
+    let message = lines.join("\n");
+    let message = message.trim();
+    let message = match message.strip_prefix('`').and_then(|m| m.strip_suffix('`')) {
+        Some(inner) if !message.contains('\n') && !inner.contains('`') => inner,
+        _ => message,
+    };
+
+    wrap_body(message)
+}
+
+/// What is wrong with a message that should be a conventional commit but
+/// does not parse as one, empty if it does or no conventional commit is
+/// required.
+pub fn validation_errors(message: &str, rules: &Rules) -> Vec<String> {
+    let is_conventional =
+        ConventionalCommit::parse(message).is_some_and(|commit| commit.has_known_type());
+    if !rules.conventional || is_conventional {
+        return Vec::new();
+    }
+
+    lint::lint(message, rules)
+        .into_iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .map(|problem| problem.message)
+        .collect()
+}
+
+fn strip_preamble(lines: &mut Vec<&str>) {
+    while lines.first().is_some_and(|line| {
+        let line = line.trim().trim_matches('*').trim();
+        line.is_empty() || PREAMBLE.is_match(line)
+    }) {
+        lines.remove(0);
+    }
+}
+
+/// Separates the subject from the body and wraps body lines longer than
+/// `BODY_WIDTH`, with a hanging indent for list items.
+fn wrap_body(message: &str) -> String {
+    let mut lines = message.lines();
+    let subject = lines.next().unwrap_or_default().trim_end();
+    let body: Vec<&str> = lines.skip_while(|line| line.trim().is_empty()).collect();
+    if body.is_empty() {
+        return subject.to_string();
+    }
+
+    let mut wrapped = Vec::new();
+    for line in body {
+        wrapped.extend(wrap_line(line.trim_end()));
+    }
+    format!("{}\n\n{}", subject, wrapped.join("\n"))
+}
+
+fn wrap_line(line: &str) -> Vec<String> {
+    let prefix_length = match BULLET.captures(line) {
+        Some(captures) => captures[0].len(),
+        None => line.len() - line.trim_start().len(),
+    };
+    // Indented lines are usually code, which wrapping would break
+    let is_code = line.starts_with('\t') || (prefix_length >= 4 && !BULLET.is_match(line));
+    if line.chars().count() <= BODY_WIDTH || is_code {
+        return vec![line.to_string()];
+    }
+
+    let indent = " ".repeat(prefix_length);
+    let mut lines = Vec::new();
+    let mut current = line[..prefix_length].to_string();
+    let mut current_width = current.chars().count();
+    for word in line[prefix_length..].split_whitespace() {
+        let word_width = word.chars().count();
+        if current_width > prefix_length && current_width + 1 + word_width > BODY_WIDTH {
+            lines.push(current);
+            current = indent.clone();
+            current_width = prefix_length;
+        }
+        if current_width > prefix_length {
+            current.push(' ');
+            current_width += 1;
+        }
+        current.push_str(word);
+        current_width += word_width;
+    }
+    lines.push(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_strips_fences_and_preambles() {
+        assert_eq!(repair("```\nAdd login form\n```"), "Add login form");
+        assert_eq!(
+            repair(
+                "Here is a commit message:\n\n```text\nAdd login form\n\nWith a body.\n```\n\nThis message follows the conventions."
+            ),
+            "Add login form\n\nWith a body."
+        );
+        assert_eq!(
+            repair(
+                "Sure! Here's a conventional commit message for these changes:\n\nfeat: add login form"
+            ),
+            "feat: add login form"
+        );
+        assert_eq!(
+            repair("**Commit message:**\nfix: resolve race"),
+            "fix: resolve race"
+        );
+        assert_eq!(repair("`Add login form`"), "Add login form");
+        assert_eq!(
+            repair("Add commit message generation."),
+            "Add commit message generation."
+        );
+        assert_eq!(
+            repair("Add login form\n\nUse `Form` for it."),
+            "Add login form\n\nUse `Form` for it."
+        );
+    }
+
+    #[test]
+    fn test_repair_wraps_body() {
+        assert_eq!(
+            repair(
+                "Add login form\nUsers can sign in with a password, or with a magic link sent to their email address."
+            ),
+            "Add login form\n\n\
+             Users can sign in with a password, or with a magic link sent to their\n\
+             email address."
+        );
+        assert_eq!(
+            repair(
+                "Add login form\n\n- Validate the email address and the password before the form is submitted\n\n    let form = LoginForm::new(validator, submit_handler, on_error_callback_fn);\n\nhttps://example.com/a/very/long/link/that/cannot/be/wrapped/at/seventy/two/columns"
+            ),
+            "Add login form\n\n\
+             - Validate the email address and the password before the form is\n  \
+             submitted\n\n    \
+             let form = LoginForm::new(validator, submit_handler, on_error_callback_fn);\n\n\
+             https://example.com/a/very/long/link/that/cannot/be/wrapped/at/seventy/two/columns"
+        );
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let rules = Rules {
+            conventional: true,
+            ..Rules::default()
+        };
+
+        assert!(validation_errors("feat: add login form", &rules).is_empty());
+        assert!(validation_errors("Add login form", &Rules::default()).is_empty());
+        assert_eq!(
+            validation_errors("Add login form", &rules),
+            vec!["The subject must have the form '<type>[optional scope][!]: <description>'"]
+        );
+        assert_eq!(
+            validation_errors("feature: add login form", &rules),
+            vec![
+                "Unknown type 'feature', use one of: feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert"
+            ]
+        );
+    }
+}
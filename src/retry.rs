@@ -0,0 +1,320 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::http::{ChunkCallback, HttpClient};
+
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: MAX_ATTEMPTS,
+            base_delay: BASE_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// How long to wait after the given (1-based) failed attempt. A delay
+    /// requested by the server wins; otherwise the exponential backoff is
+    /// jittered to somewhere between half and all of its value.
+    pub fn delay(&self, attempt: u32, error: &Error) -> Duration {
+        if let Some(retry_after) = error.api_error().and_then(|e| e.retry_after) {
+            return retry_after.min(self.max_delay);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+pub type RetryNotifier = Box<dyn Fn(&Error, u32, Duration) + Send + Sync>;
+
+/// Retries requests that failed with a retryable error, see
+/// `Error::is_retryable`.
+pub struct RetryingHttpClient<H: HttpClient> {
+    inner: H,
+    policy: RetryPolicy,
+    notifier: Option<RetryNotifier>,
+}
+
+impl<H: HttpClient> RetryingHttpClient<H> {
+    pub fn new(inner: H, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            notifier: None,
+        }
+    }
+
+    /// Called with the error, the attempt that failed and the delay before
+    /// the next one.
+    pub fn with_notifier(mut self, notifier: RetryNotifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    async fn wait_before_retry(&self, attempt: u32, error: &Error) {
+        let delay = self.policy.delay(attempt, error);
+        if let Some(notifier) = &self.notifier {
+            notifier(error, attempt, delay);
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: HttpClient> HttpClient for RetryingHttpClient<H> {
+    async fn post_json<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+    ) -> Result<String, Error> {
+        let mut attempt = 1;
+        loop {
+            match self.inner.post_json(url, headers.clone(), body).await {
+                Err(error) if error.is_retryable() && attempt < self.policy.max_attempts => {
+                    self.wait_before_retry(attempt, &error).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn post_json_stream<T: Serialize + Send + Sync>(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: &T,
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<(), Error> {
+        let mut attempt = 1;
+        loop {
+            let mut received = false;
+            let result = self
+                .inner
+                .post_json_stream(url, headers.clone(), body, &mut |chunk| {
+                    received = true;
+                    on_chunk(chunk);
+                })
+                .await;
+
+            // Once part of the body has been handed out, a retry would
+            // deliver it twice
+            match result {
+                Err(error)
+                    if !received && error.is_retryable() && attempt < self.policy.max_attempts =>
+                {
+                    self.wait_before_retry(attempt, &error).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    struct ScriptedHttpClient {
+        responses: Mutex<VecDeque<Result<String, Error>>>,
+        calls: Mutex<u32>,
+    }
+
+    impl ScriptedHttpClient {
+        fn new(responses: Vec<Result<String, Error>>) -> Self {
+            Self {
+                responses: Mutex::new(responses.into()),
+                calls: Mutex::new(0),
+            }
+        }
+
+        fn calls(&self) -> u32 {
+            *self.calls.lock().unwrap()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl HttpClient for ScriptedHttpClient {
+        async fn post_json<T: Serialize + Send + Sync>(
+            &self,
+            _url: &str,
+            _headers: Vec<(String, String)>,
+            _body: &T,
+        ) -> Result<String, Error> {
+            *self.calls.lock().unwrap() += 1;
+            self.responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("unexpected request")
+        }
+    }
+
+    fn rate_limited(retry_after: Option<Duration>) -> Error {
+        Error::RateLimit(ApiError {
+            status: Some(429),
+            retry_after,
+            ..ApiError::default()
+        })
+    }
+
+    fn instant_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let scripted = ScriptedHttpClient::new(vec![
+            Err(rate_limited(None)),
+            Err(Error::Overloaded(ApiError::default())),
+            Ok("done".to_string()),
+        ]);
+        let client = RetryingHttpClient::new(scripted, instant_policy(4));
+
+        let response = client.post_json("url", vec![], &()).await.unwrap();
+
+        assert_eq!(response, "done");
+        assert_eq!(client.inner.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let scripted = ScriptedHttpClient::new(vec![
+            Err(rate_limited(None)),
+            Err(rate_limited(None)),
+            Ok("too late".to_string()),
+        ]);
+        let client = RetryingHttpClient::new(scripted, instant_policy(2));
+
+        let result = client.post_json("url", vec![], &()).await;
+
+        assert!(matches!(result, Err(Error::RateLimit(_))));
+        assert_eq!(client.inner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let scripted =
+            ScriptedHttpClient::new(vec![Err(Error::Authentication(ApiError::default()))]);
+        let client = RetryingHttpClient::new(scripted, instant_policy(4));
+
+        let result = client.post_json("url", vec![], &()).await;
+
+        assert!(matches!(result, Err(Error::Authentication(_))));
+        assert_eq!(client.inner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_timeouts() {
+        let scripted =
+            ScriptedHttpClient::new(vec![Err(Error::Timeout), Ok("too late".to_string())]);
+        let client = RetryingHttpClient::new(scripted, instant_policy(4));
+
+        let result = client.post_json("url", vec![], &()).await;
+
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(client.inner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_notifies_before_each_retry() {
+        let scripted = ScriptedHttpClient::new(vec![
+            Err(Error::Network("connection reset".to_string())),
+            Err(Error::Overloaded(ApiError::default())),
+            Ok("done".to_string()),
+        ]);
+        let attempts = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let recorded = attempts.clone();
+        let client = RetryingHttpClient::new(scripted, instant_policy(4)).with_notifier(Box::new(
+            move |_, attempt, _| recorded.lock().unwrap().push(attempt),
+        ));
+
+        client.post_json("url", vec![], &()).await.unwrap();
+
+        assert_eq!(*attempts.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_retries_before_any_chunk() {
+        let scripted = ScriptedHttpClient::new(vec![
+            Err(Error::Network("connection reset".to_string())),
+            Ok("data".to_string()),
+        ]);
+        let client = RetryingHttpClient::new(scripted, instant_policy(4));
+
+        let mut received = Vec::new();
+        client
+            .post_json_stream("url", vec![], &(), &mut |chunk| {
+                received.extend_from_slice(chunk)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(received, b"data");
+        assert_eq!(client.inner.calls(), 2);
+    }
+
+    #[test]
+    fn test_delay_honors_retry_after() {
+        let policy = RetryPolicy::default();
+
+        let delay = policy.delay(1, &rate_limited(Some(Duration::from_secs(7))));
+        assert_eq!(delay, Duration::from_secs(7));
+
+        let delay = policy.delay(1, &rate_limited(Some(Duration::from_secs(600))));
+        assert_eq!(delay, MAX_DELAY);
+    }
+
+    #[test]
+    fn test_delay_backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy::default();
+
+        for attempt in 1..=3 {
+            let backoff = BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = policy.delay(attempt, &Error::Timeout);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?}", delay);
+        }
+
+        let delay = policy.delay(30, &Error::Timeout);
+        assert!(delay >= MAX_DELAY / 2 && delay <= MAX_DELAY);
+    }
+
+    #[test]
+    fn test_with_max_attempts_allows_at_least_one() {
+        assert_eq!(RetryPolicy::default().with_max_attempts(0).max_attempts, 1);
+        assert_eq!(RetryPolicy::default().with_max_attempts(6).max_attempts, 6);
+    }
+}
@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::git::{git_at, rev_list_at};
+
+/// A commit and the message it gets instead of its current one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewording {
+    pub commit: String,
+    pub old_message: String,
+    pub new_message: String,
+}
+
+/// The commits in `range`, oldest first.
+pub fn commits_at(path: &Path, range: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let commits = rev_list_at(path, &["--reverse", range])?;
+    if commits.is_empty() {
+        return Err(format!("No commits in '{}'", range).into());
+    }
+    if !rev_list_at(path, &["--min-parents=2", range])?.is_empty() {
+        return Err(format!(
+            "'{}' contains merge commits, which cannot be reworded",
+            range
+        )
+        .into());
+    }
+
+    Ok(commits)
+}
+
+pub fn message_at(path: &Path, commit: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let output = git_at(path, &["log", "-1", "--format=%B", commit])?;
+    Ok(output.trim().to_string())
+}
+
+/// The changes a commit made, the root commit included.
+pub fn diff_at(path: &Path, commit: &str) -> Result<String, Box<dyn std::error::Error>> {
+    git_at(
+        path,
+        &["diff-tree", "-p", "-r", "--root", "--no-commit-id", commit],
+    )
+}
+
+/// Whether tracked files have changes, staged or not, that a rebase would
+/// refuse to run with.
+pub fn has_changes_at(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let output = git_at(path, &["status", "--porcelain", "--untracked-files=no"])?;
+    Ok(!output.trim().is_empty())
+}
+
+/// Lines for a before and after table of the subjects.
+pub fn table(rewordings: &[Rewording]) -> Vec<String> {
+    let subject = |message: &str| message.lines().next().unwrap_or_default().to_string();
+    let width = rewordings
+        .iter()
+        .map(|rewording| subject(&rewording.old_message).chars().count())
+        .chain(["Before".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!("{:<7}  {:<width$}  After", "Commit", "Before")];
+    for rewording in rewordings {
+        lines.push(format!(
+            "{:<7}  {:<width$}  {}",
+            rewording.commit.chars().take(7).collect::<String>(),
+            subject(&rewording.old_message),
+            subject(&rewording.new_message),
+        ));
+    }
+    lines
+}
+
+/// Rewrites the current branch with an interactive rebase whose todo list
+/// amends each reworded commit right after it is picked. The range must be
+/// part of the current branch without merges from its oldest commit on.
+pub fn rewrite_at(path: &Path, rewordings: &[Rewording]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(oldest) = rewordings.first() else {
+        return Ok(());
+    };
+
+    let parent = format!("{}^", oldest.commit);
+    let has_parent = git_at(path, &["rev-parse", "--verify", "--quiet", &parent]).is_ok();
+    let branch_range = if has_parent {
+        format!("{}..HEAD", parent)
+    } else {
+        "HEAD".to_string()
+    };
+    let branch = rev_list_at(path, &["--reverse", &branch_range])?;
+    if !rev_list_at(path, &["--min-parents=2", &branch_range])?.is_empty() {
+        return Err(
+            "The current branch has merge commits after the range, rebasing would flatten them"
+                .into(),
+        );
+    }
+    if let Some(rewording) = rewordings
+        .iter()
+        .find(|rewording| !branch.contains(&rewording.commit))
+    {
+        return Err(format!("Commit {} is not on the current branch", rewording.commit).into());
+    }
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut todo = String::new();
+    for commit in &branch {
+        todo.push_str(&format!("pick {}\n", commit));
+        if let Some(rewording) = rewordings
+            .iter()
+            .find(|rewording| &rewording.commit == commit)
+        {
+            let message_file = temp_dir.path().join(commit);
+            fs::write(&message_file, format!("{}\n", rewording.new_message))?;
+            // The user's hooks, gitai's commit-msg lint included, must not
+            // stop the rebase halfway through history nobody edited by hand
+            todo.push_str(&format!(
+                "exec git commit --amend --allow-empty --no-verify --quiet -F {}\n",
+                quote(&message_file.display().to_string())
+            ));
+        }
+    }
+    let todo_file = temp_dir.path().join("git-rebase-todo");
+    fs::write(&todo_file, todo)?;
+
+    // Git calls the sequence editor with the path of its own todo list
+    let mut cmd = Command::new("git");
+    cmd.current_dir(path)
+        .env(
+            "GIT_SEQUENCE_EDITOR",
+            format!("cp {}", quote(&todo_file.display().to_string())),
+        )
+        .args(["rebase", "--interactive", "--quiet"]);
+    if has_parent {
+        cmd.arg(&parent);
+    } else {
+        cmd.arg("--root");
+    }
+
+    // Captured so the "Executing: ..." lines only show when something failed
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "{}\nThe rebase stopped before all commits were reworded. See 'git status', or run 'git rebase --abort' to undo it.",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+        git_at(dir, args)
+    }
+
+    fn repository_with_commits(messages: &[&str]) -> Result<TempDir, Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"])?;
+        git(repo, &["config", "user.name", "Test"])?;
+        git(repo, &["config", "user.email", "test@example.com"])?;
+        for (index, message) in messages.iter().enumerate() {
+            fs::write(repo.join(format!("{}.txt", index)), message)?;
+            git(repo, &["add", "."])?;
+            git(repo, &["commit", "-q", "-m", message])?;
+        }
+        Ok(temp_dir)
+    }
+
+    fn log(repo: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(git(repo, &["log", "--reverse", "--format=%s"])?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn test_commits_and_diff() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = repository_with_commits(&["Initial", "wip", "more wip"])?;
+        let repo = temp_dir.path();
+
+        let commits = commits_at(repo, "HEAD~2..")?;
+        assert_eq!(commits.len(), 2);
+        assert_eq!(message_at(repo, &commits[0])?, "wip");
+        assert!(diff_at(repo, &commits[1])?.starts_with("diff --git a/2.txt b/2.txt"));
+
+        let root = commits_at(repo, "HEAD~2")?;
+        assert!(diff_at(repo, &root[0])?.contains("+Initial"));
+
+        assert!(commits_at(repo, "HEAD..").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_rewords_only_the_range() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = repository_with_commits(&["Initial", "wip", "more wip", "Add docs"])?;
+        let repo = temp_dir.path();
+        let tree = git(repo, &["rev-parse", "HEAD^{tree}"])?;
+        // Hooks that reject every commit are skipped
+        #[cfg(unix)]
+        for hook in ["pre-commit", "commit-msg"] {
+            use std::os::unix::fs::PermissionsExt;
+            let hook_file = repo.join(".git/hooks").join(hook);
+            fs::write(&hook_file, "#!/bin/sh\nexit 1\n")?;
+            fs::set_permissions(&hook_file, fs::Permissions::from_mode(0o755))?;
+        }
+
+        let rewordings = commits_at(repo, "HEAD~3..HEAD~1")?
+            .into_iter()
+            .zip(["Add parser\n\nWith a body.", "Add it's tests"])
+            .map(|(commit, new_message)| Rewording {
+                old_message: message_at(repo, &commit).unwrap(),
+                commit,
+                new_message: new_message.to_string(),
+            })
+            .collect::<Vec<_>>();
+        rewrite_at(repo, &rewordings)?;
+
+        assert_eq!(
+            log(repo)?,
+            vec!["Initial", "Add parser", "Add it's tests", "Add docs"]
+        );
+        assert_eq!(message_at(repo, "HEAD~2")?, "Add parser\n\nWith a body.");
+        assert_eq!(git(repo, &["rev-parse", "HEAD^{tree}"])?, tree);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_root_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = repository_with_commits(&["wip", "Add docs"])?;
+        let repo = temp_dir.path();
+
+        let commit = commits_at(repo, "HEAD~1")?.remove(0);
+        rewrite_at(
+            repo,
+            &[Rewording {
+                commit,
+                old_message: "wip".to_string(),
+                new_message: "Add readme".to_string(),
+            }],
+        )?;
+
+        assert_eq!(log(repo)?, vec!["Add readme", "Add docs"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_table() {
+        let rewordings = vec![
+            Rewording {
+                commit: "0123456789abcdef".to_string(),
+                old_message: "wip".to_string(),
+                new_message: "Add parser\n\nWith a body.".to_string(),
+            },
+            Rewording {
+                commit: "fedcba9876543210".to_string(),
+                old_message: "fix the thing".to_string(),
+                new_message: "Fix off-by-one in the tokenizer".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            table(&rewordings),
+            vec![
+                "Commit   Before         After",
+                "0123456  wip            Add parser",
+                "fedcba9  fix the thing  Fix off-by-one in the tokenizer",
+            ]
+        );
+    }
+}
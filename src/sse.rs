@@ -0,0 +1,148 @@
+/// A single server-sent event, see
+/// https://html.spec.whatwg.org/multipage/server-sent-events.html
+#[derive(Debug, PartialEq, Eq)]
+pub struct Event {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incrementally turns raw response bytes into events. Chunks may split
+/// events (or UTF-8 sequences) at any byte, so incomplete input is buffered
+/// until the blank line terminating the event arrives.
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer
+            .extend(chunk.iter().copied().filter(|&byte| byte != b'\r'));
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let raw: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(event) = parse_event(&String::from_utf8_lossy(&raw[..end])) {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    /// Flushes an event left unterminated when the stream closed.
+    pub fn finish(&mut self) -> Option<Event> {
+        let raw = std::mem::take(&mut self.buffer);
+        parse_event(&String::from_utf8_lossy(&raw))
+    }
+}
+
+fn parse_event(raw: &str) -> Option<Event> {
+    let mut event = None;
+    let mut data: Option<String> = None;
+
+    for line in raw.lines() {
+        if line.is_empty() || line.starts_with(':') {
+            continue;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match field {
+            "event" => event = Some(value.to_string()),
+            "data" => match &mut data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => data = Some(value.to_string()),
+            },
+            _ => {}
+        }
+    }
+
+    if event.is_none() && data.is_none() {
+        return None;
+    }
+
+    Some(Event {
+        event,
+        data: data.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_single_event() {
+        let mut decoder = Decoder::new();
+
+        let events = decoder.feed(b"event: ping\ndata: {\"type\": \"ping\"}\n\n");
+
+        assert_eq!(
+            events,
+            vec![Event {
+                event: Some("ping".to_string()),
+                data: "{\"type\": \"ping\"}".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode_event_split_across_chunks() {
+        let mut decoder = Decoder::new();
+
+        assert!(decoder.feed(b"event: message_stop\nda").is_empty());
+        assert!(decoder.feed(b"ta: {}\n").is_empty());
+        let events = decoder.feed(b"\nevent: ping\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.as_deref(), Some("message_stop"));
+        assert_eq!(events[0].data, "{}");
+    }
+
+    #[test]
+    fn test_decode_split_utf8_sequence() {
+        let mut decoder = Decoder::new();
+        let payload = "data: héllo\n\n".as_bytes();
+        let split = payload.iter().position(|&b| b == 0xC3).unwrap() + 1;
+
+        assert!(decoder.feed(&payload[..split]).is_empty());
+        let events = decoder.feed(&payload[split..]);
+
+        assert_eq!(events[0].data, "héllo");
+    }
+
+    #[test]
+    fn test_decode_crlf_comments_and_multiline_data() {
+        let mut decoder = Decoder::new();
+
+        let events = decoder.feed(b": keep-alive\r\n\r\ndata: first\r\ndata: second\r\n\r\n");
+
+        assert_eq!(
+            events,
+            vec![Event {
+                event: None,
+                data: "first\nsecond".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_finish_flushes_unterminated_event() {
+        let mut decoder = Decoder::new();
+
+        assert!(decoder.feed(b"event: message_stop\ndata: {}").is_empty());
+
+        let event = decoder.finish().unwrap();
+        assert_eq!(event.event.as_deref(), Some("message_stop"));
+        assert!(decoder.finish().is_none());
+    }
+}